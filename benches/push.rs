@@ -0,0 +1,57 @@
+//! Benchmarks for `StreamdownRenderer::push` across token sizes.
+//!
+//! Covers single-byte tokens (worst case for per-push overhead) up to a
+//! single 1 MB chunk (worst case for per-line buffering).
+
+use std::io;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use forge_markdown_stream::{RendererConfig, StreamdownRenderer, Theme};
+
+const TOKEN_SIZES: [usize; 6] = [1, 16, 256, 4 * 1024, 64 * 1024, 1024 * 1024];
+
+/// Build an ASCII-only markdown document of roughly `size` bytes, so it can
+/// be split into tokens at any byte offset.
+fn document(size: usize) -> String {
+    let section = "## Section\n\n\
+        Some prose with **bold**, *italic* and `code` spans that wraps across \
+        the terminal width once rendered.\n\n\
+        - first item\n\
+        - second item with a [link](https://example.com)\n\n\
+        ```rust\n\
+        fn main() {\n    println!(\"hello\");\n}\n\
+        ```\n\n";
+    section.repeat(size / section.len() + 1)
+}
+
+fn push_tokens(c: &mut Criterion) {
+    let input = document(1024 * 1024);
+    let config = RendererConfig::new().width(80).theme(Theme::dark());
+    let mut renderer = StreamdownRenderer::from_config(io::sink(), config);
+
+    let mut group = c.benchmark_group("push");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(input.len() as u64));
+
+    for size in TOKEN_SIZES {
+        let tokens: Vec<&str> = input
+            .as_bytes()
+            .chunks(size)
+            .map(|chunk| std::str::from_utf8(chunk).unwrap())
+            .collect();
+
+        group.bench_with_input(BenchmarkId::from_parameter(size), &tokens, |b, tokens| {
+            b.iter(|| {
+                for token in tokens {
+                    renderer.push(token).unwrap();
+                }
+                renderer.end_message().unwrap()
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, push_tokens);
+criterion_main!(benches);
@@ -6,7 +6,7 @@ use std::thread::{self, JoinHandle};
 use std::time::Duration;
 
 use indicatif::{ProgressBar, ProgressStyle};
-use forge_markdown_stream::StreamdownRenderer;
+use forge_markdown_stream::{RendererConfig, StreamdownRenderer};
 
 /// A spinner that only shows during idle periods (no content for a while).
 struct Spinner {
@@ -145,7 +145,8 @@ fn main() -> io::Result<()> {
     let content = include_str!("data.md");
     let tokens: Vec<&str> = content.split("<separator>").collect();
     let writer = CharWriter::new(1);
-    let mut renderer = StreamdownRenderer::new(writer, 80);
+    let config = RendererConfig::new().max_width(100);
+    let mut renderer = StreamdownRenderer::from_config(writer, config);
 
     for token in &tokens {
         renderer.push(token)?;
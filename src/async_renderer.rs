@@ -0,0 +1,203 @@
+//! Async streaming adapter for `futures` streams and `tokio` writers.
+
+use std::io::{self, Write};
+
+use futures::{Stream, StreamExt};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use crate::{
+    CodeBlock, Error, EventHook, FlushPolicy, RenderStats, RendererConfig, StreamdownRenderer,
+    Theme,
+};
+
+/// Async counterpart of [`StreamdownRenderer`].
+///
+/// Events go through the same `Parser`/`Renderer` pipeline, rendered into an
+/// in-memory buffer that is written to the async writer after every push.
+/// Each push awaits that write, so a slow writer applies backpressure to the
+/// token source instead of letting output pile up in memory. The async writer
+/// is flushed according to the configured [`FlushPolicy`].
+pub struct AsyncStreamdownRenderer<W: AsyncWrite + Unpin> {
+    inner: StreamdownRenderer<OutputBuffer>,
+    writer: W,
+}
+
+/// In-memory output that records flush requests from the sync renderer.
+#[derive(Default)]
+struct OutputBuffer {
+    data: Vec<u8>,
+    flush_requested: bool,
+}
+
+impl Write for OutputBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.data.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flush_requested = true;
+        Ok(())
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncStreamdownRenderer<W> {
+    /// Create a new renderer with the given writer and terminal width.
+    pub fn new(writer: W, width: usize) -> Self {
+        Self::from_config(writer, RendererConfig::new().width(width))
+    }
+
+    /// Create a new renderer with a custom theme.
+    pub fn with_theme(writer: W, width: usize, theme: Theme) -> Self {
+        Self::from_config(writer, RendererConfig::new().width(width).theme(theme))
+    }
+
+    /// Create a new renderer from a configuration.
+    pub fn from_config(writer: W, config: RendererConfig) -> Self {
+        Self {
+            inner: StreamdownRenderer::from_config(OutputBuffer::default(), config),
+            writer,
+        }
+    }
+
+    /// Enable or disable live preview of the in-progress line.
+    pub fn set_live_preview(&mut self, enabled: bool) {
+        self.inner.set_live_preview(enabled);
+    }
+
+    /// Set the terminal width.
+    pub fn set_width(&mut self, width: usize) {
+        self.inner.set_width(width);
+    }
+
+    /// Add a hook that is invoked for every parse event.
+    pub fn add_hook(&mut self, hook: impl EventHook + 'static) {
+        self.inner.add_hook(hook);
+    }
+
+    /// Set when the async writer is flushed.
+    pub fn set_flush_policy(&mut self, policy: FlushPolicy) {
+        self.inner.set_flush_policy(policy);
+    }
+
+    /// Push a token and write any rendered output.
+    pub async fn push(&mut self, token: &str) -> io::Result<()> {
+        self.inner.push(token)?;
+        self.drain().await
+    }
+
+    /// Push raw bytes and write any rendered output.
+    ///
+    /// See [`StreamdownRenderer::push_bytes`] for UTF-8 boundary handling.
+    pub async fn push_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        let result = self.inner.push_bytes(bytes);
+        self.drain().await?;
+        result
+    }
+
+    /// Push every token from a stream, waiting for each write to complete
+    /// before pulling the next token.
+    pub async fn push_stream<S>(&mut self, stream: S) -> io::Result<()>
+    where
+        S: Stream,
+        S::Item: AsRef<str>,
+    {
+        let mut stream = std::pin::pin!(stream);
+        while let Some(token) = stream.next().await {
+            self.push(token.as_ref()).await?;
+        }
+        Ok(())
+    }
+
+    /// Finish rendering, flushing any remaining buffered content.
+    /// Returns the underlying writer.
+    pub async fn finish(mut self) -> io::Result<W> {
+        self.inner.render_remaining()?;
+        self.inner.flush()?;
+        self.drain().await?;
+        Ok(self.writer)
+    }
+
+    /// End the current message, keeping the renderer for the next one.
+    ///
+    /// See [`StreamdownRenderer::end_message`].
+    pub async fn end_message(&mut self) -> io::Result<RenderStats> {
+        let result = self.inner.end_message();
+        self.drain().await?;
+        result
+    }
+
+    /// Interrupt the current message, closing open blocks.
+    ///
+    /// See [`StreamdownRenderer::interrupt`].
+    pub async fn interrupt(&mut self) -> io::Result<RenderStats> {
+        let result = self.inner.interrupt();
+        self.drain().await?;
+        result
+    }
+
+    /// Take the code blocks completed so far.
+    pub fn take_code_blocks(&mut self) -> Vec<CodeBlock> {
+        self.inner.take_code_blocks()
+    }
+
+    /// Consume the renderer, returning the underlying writer.
+    ///
+    /// Unlike [`AsyncStreamdownRenderer::finish`], buffered content is discarded.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Write everything rendered so far to the async writer.
+    async fn drain(&mut self) -> io::Result<()> {
+        let buffer = self.inner.get_mut();
+        if !buffer.data.is_empty() {
+            self.writer.write_all(&buffer.data).await?;
+            buffer.data.clear();
+        }
+        if std::mem::take(&mut buffer.flush_requested) {
+            self.writer.flush().await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_push_writes_complete_lines() {
+        let mut renderer = AsyncStreamdownRenderer::new(Vec::new(), 80);
+        renderer.push("Hello ").await.unwrap();
+        renderer.push("world\n").await.unwrap();
+        let output = renderer.finish().await.unwrap();
+        assert!(String::from_utf8(output).unwrap().contains("Hello world"));
+    }
+
+    #[tokio::test]
+    async fn test_push_stream() {
+        let tokens = futures::stream::iter(vec!["one ".to_string(), "two\n".to_string()]);
+        let mut renderer = AsyncStreamdownRenderer::new(Vec::new(), 80);
+        renderer.push_stream(tokens).await.unwrap();
+        let output = renderer.finish().await.unwrap();
+        assert!(String::from_utf8(output).unwrap().contains("one two"));
+    }
+
+    #[tokio::test]
+    async fn test_manual_flush_policy_still_writes() {
+        let mut renderer = AsyncStreamdownRenderer::new(Vec::new(), 80);
+        renderer.set_flush_policy(FlushPolicy::Manual);
+        renderer.push("buffered\n").await.unwrap();
+        assert!(String::from_utf8_lossy(&renderer.writer).contains("buffered"));
+        assert!(!renderer.inner.get_mut().flush_requested);
+    }
+
+    #[tokio::test]
+    async fn test_finish_renders_partial_line() {
+        let mut renderer = AsyncStreamdownRenderer::new(Vec::new(), 80);
+        renderer.push("no newline").await.unwrap();
+        let output = renderer.finish().await.unwrap();
+        assert!(String::from_utf8(output).unwrap().contains("no newline"));
+    }
+}
@@ -1,117 +1,1419 @@
 //! Code block rendering with syntax highlighting and line wrapping.
 
-use crate::utils::{detect_theme_mode, ThemeMode};
-use streamdown_render::code::code_wrap;
-use syntect::easy::HighlightLines;
-use syntect::highlighting::ThemeSet;
-use syntect::parsing::SyntaxSet;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::{Range, RangeInclusive};
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+
+use crate::diff::{is_diff_label, style_escape, DiffClassifier, DiffLine, DiffStyles};
+use crate::error::Error;
+use crate::language::{default_aliases, detect_syntax, resolve_syntax};
+use crate::session::{is_session_label, SessionClassifier, SessionLine};
+use crate::theme::{Style as TextStyle, Theme as TextTheme};
+use crate::utils::ThemeMode;
+use streamdown_ansi::utils::visible_length;
+use syntect::dumps::from_dump_file;
+use syntect::highlighting::{
+    HighlightIterator, HighlightState, Highlighter, Style, Theme, ThemeSet,
+};
+use syntect::parsing::{ParseState, ScopeStack, SyntaxSet};
 use syntect::util::as_24_bit_terminal_escaped;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 const RESET: &str = "\x1b[0m";
 
+/// Minimum digits of the line number gutter.
+const MIN_NUMBER_WIDTH: usize = 3;
+
+/// Marker appended to truncated code lines.
+const TRUNCATION_MARKER: &str = "…";
+
+/// Syntax and theme sets used for highlighting code blocks.
+///
+/// Loading the default sets takes tens of milliseconds and several megabytes,
+/// so they are loaded once per process and shared between renderers. Cloning
+/// is cheap: the sets are behind an [`Arc`].
+#[derive(Clone)]
+pub struct HighlightAssets {
+    syntax_set: Arc<SyntaxSet>,
+    theme_set: Arc<ThemeSet>,
+}
+
+impl HighlightAssets {
+    /// Use custom syntax and theme sets.
+    pub fn new(syntax_set: impl Into<Arc<SyntaxSet>>, theme_set: impl Into<Arc<ThemeSet>>) -> Self {
+        Self {
+            syntax_set: syntax_set.into(),
+            theme_set: theme_set.into(),
+        }
+    }
+
+    /// The default sets, loaded on first use and shared across the process.
+    pub fn shared() -> Self {
+        static SHARED: OnceLock<HighlightAssets> = OnceLock::new();
+        SHARED
+            .get_or_init(|| {
+                Self::new(
+                    SyntaxSet::load_defaults_newlines(),
+                    ThemeSet::load_defaults(),
+                )
+            })
+            .clone()
+    }
+
+    /// Add the `.sublime-syntax` files in a folder to the syntax set.
+    ///
+    /// The new syntaxes are merged with the existing ones, so fences naming
+    /// either resolve. Folders are searched recursively.
+    pub fn with_syntax_folder(self, path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let mut builder = (*self.syntax_set).clone().into_builder();
+        builder
+            .add_from_folder(path, true)
+            .map_err(|err| Error::SyntaxLoad {
+                path: path.to_path_buf(),
+                source: Box::new(err),
+            })?;
+        Ok(Self {
+            syntax_set: Arc::new(builder.build()),
+            ..self
+        })
+    }
+
+    /// Add the syntaxes of a precompiled syntax set dump to the syntax set.
+    ///
+    /// The dump must be created with [`syntect::dumps::dump_to_file`] from a
+    /// [`SyntaxSet`] built with newline-terminated lines.
+    pub fn with_syntax_dump(self, path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let extra: SyntaxSet = from_dump_file(path).map_err(|err| Error::SyntaxLoad {
+            path: path.to_path_buf(),
+            source: err,
+        })?;
+        let mut builder = (*self.syntax_set).clone().into_builder();
+        for syntax in extra.into_builder().syntaxes() {
+            builder.add(syntax.clone());
+        }
+        Ok(Self {
+            syntax_set: Arc::new(builder.build()),
+            ..self
+        })
+    }
+
+    /// The syntax set.
+    pub fn syntax_set(&self) -> &SyntaxSet {
+        &self.syntax_set
+    }
+
+    /// The theme set.
+    pub fn theme_set(&self) -> &ThemeSet {
+        &self.theme_set
+    }
+}
+
+impl Default for HighlightAssets {
+    fn default() -> Self {
+        Self::shared()
+    }
+}
+
+impl fmt::Debug for HighlightAssets {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HighlightAssets")
+            .field("syntaxes", &self.syntax_set.syntaxes().len())
+            .field("themes", &self.theme_set.themes.len())
+            .finish()
+    }
+}
+
+/// How code blocks are set off from the surrounding text.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CodeBlockStyle {
+    /// Highlighted lines only, without borders.
+    #[default]
+    None,
+    /// A top border showing the language and a bottom border.
+    Framed,
+}
+
+/// How code lines wider than the terminal are displayed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CodeOverflow {
+    /// Wrap long lines, indenting the continuation lines.
+    #[default]
+    Wrap,
+    /// Cut long lines at the width and mark them with `…`.
+    Truncate,
+    /// Write lines unchanged and let the terminal wrap them, so copied text
+    /// matches the source.
+    NoWrap,
+}
+
+/// Limit on the visible lines of long code blocks.
+///
+/// Blocks longer than `head + tail` lines show only their first `head` and
+/// last `tail` lines, around a marker counting the hidden lines. The full
+/// source is still available through [`CodeBlock`](crate::CodeBlock).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CodeCollapse {
+    /// Lines shown at the start of the block.
+    pub head: usize,
+    /// Lines shown at the end of the block.
+    pub tail: usize,
+}
+
+impl CodeCollapse {
+    /// Show the first `head` and last `tail` lines of long code blocks.
+    pub fn new(head: usize, tail: usize) -> Self {
+        Self { head, tail }
+    }
+}
+
+/// A syntect theme used to highlight code blocks.
+///
+/// Either one of the bundled themes selected by name, or a `.tmTheme` file
+/// loaded from disk.
+///
+/// # Example
+///
+/// ```no_run
+/// use forge_markdown_stream::{CodeTheme, Theme};
+///
+/// let mut theme = Theme::dark();
+/// theme.code_theme = Some(CodeTheme::named("Solarized (dark)")?);
+/// # Ok::<(), forge_markdown_stream::Error>(())
+/// ```
+#[derive(Clone)]
+pub struct CodeTheme {
+    name: String,
+    theme: Arc<Theme>,
+}
+
+impl CodeTheme {
+    /// Select one of the bundled syntect themes by name.
+    ///
+    /// Returns [`Error::UnknownCodeTheme`] listing the available themes if
+    /// no theme has that name.
+    pub fn named(name: &str) -> Result<Self, Error> {
+        Self::from_set(HighlightAssets::shared().theme_set(), name)
+    }
+
+    /// Select a theme by name from a custom theme set.
+    pub fn from_set(theme_set: &ThemeSet, name: &str) -> Result<Self, Error> {
+        match theme_set.themes.get(name) {
+            Some(theme) => Ok(Self {
+                name: name.to_string(),
+                theme: Arc::new(theme.clone()),
+            }),
+            None => Err(Error::UnknownCodeTheme {
+                name: name.to_string(),
+                available: theme_set.themes.keys().cloned().collect(),
+            }),
+        }
+    }
+
+    /// Load a `.tmTheme` file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let theme = ThemeSet::get_theme(path).map_err(|source| Error::CodeThemeLoad {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let name = theme
+            .name
+            .clone()
+            .unwrap_or_else(|| path.display().to_string());
+        Ok(Self {
+            name,
+            theme: Arc::new(theme),
+        })
+    }
+
+    /// The theme name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl From<Theme> for CodeTheme {
+    fn from(theme: Theme) -> Self {
+        let name = theme.name.clone().unwrap_or_default();
+        Self {
+            name,
+            theme: Arc::new(theme),
+        }
+    }
+}
+
+impl fmt::Debug for CodeTheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("CodeTheme").field(&self.name).finish()
+    }
+}
+
+/// Highlighting state carried from one line of a code block to the next.
+struct BlockState {
+    parse: ParseState,
+    highlight: HighlightState,
+    /// The block has no usable label; detect its syntax from the first line.
+    detect: bool,
+    /// Number of the last rendered line, counted from 1.
+    line: usize,
+    /// Digits of the line number gutter. Only grows within a block, so the
+    /// gutter never shifts back.
+    number_width: usize,
+    /// Lines to emphasize.
+    emphasized: Vec<RangeInclusive<usize>>,
+    /// Classifier for diff blocks, whose `parse` state highlights the code
+    /// being patched.
+    diff: Option<DiffClassifier>,
+    /// Classifier for shell session blocks, whose `parse` state highlights
+    /// the commands.
+    session: Option<SessionClassifier>,
+}
+
 /// Code block highlighter using syntect.
 pub struct CodeHighlighter {
-    syntax_set: SyntaxSet,
-    theme_set: ThemeSet,
+    assets: HighlightAssets,
+    code_theme: Option<CodeTheme>,
+    aliases: HashMap<String, String>,
+    background: bool,
+    line_numbers: bool,
+    line_number_style: TextStyle,
+    emphasis_style: TextStyle,
+    diff_styles: DiffStyles,
+    diff_highlighting: bool,
+    prompt_style: TextStyle,
+    output_style: TextStyle,
+    overflow: CodeOverflow,
+    copy_safe: bool,
     theme_mode: ThemeMode,
+    block: Option<BlockState>,
 }
 
 impl Default for CodeHighlighter {
     fn default() -> Self {
-        Self {
-            syntax_set: SyntaxSet::load_defaults_newlines(),
-            theme_set: ThemeSet::load_defaults(),
-            theme_mode: detect_theme_mode(),
-        }
+        Self::new(HighlightAssets::shared())
     }
 }
 
 impl CodeHighlighter {
-    /// Highlight a single line of code.
-    fn highlight_line(&self, line: &str, language: Option<&str>) -> String {
-        let syntax = language
-            .and_then(|lang| self.syntax_set.find_syntax_by_token(lang))
-            .unwrap_or_else(|| self.syntax_set.find_syntax_plain_text());
-
-        let theme_name = match self.theme_mode {
-            ThemeMode::Dark => "base16-ocean.dark",
-            ThemeMode::Light => "InspiredGitHub",
+    /// Create a highlighter using the given syntax and theme sets.
+    pub fn new(assets: HighlightAssets) -> Self {
+        Self {
+            assets,
+            code_theme: None,
+            aliases: default_aliases(),
+            background: false,
+            line_numbers: false,
+            line_number_style: TextStyle::default(),
+            emphasis_style: TextStyle::default(),
+            diff_styles: DiffStyles::default(),
+            diff_highlighting: false,
+            prompt_style: TextStyle::default(),
+            output_style: TextStyle::default(),
+            overflow: CodeOverflow::default(),
+            copy_safe: false,
+            theme_mode: ThemeMode::Dark,
+            block: None,
+        }
+    }
+
+    /// Replace the syntax and theme sets used for subsequent code blocks.
+    pub fn set_assets(&mut self, assets: HighlightAssets) {
+        self.assets = assets;
+    }
+
+    /// Map a fence label to the syntax of another language.
+    ///
+    /// Aliases are only consulted for labels that syntect does not know.
+    /// Labels are matched case-insensitively.
+    pub fn add_language_alias(&mut self, alias: &str, language: &str) {
+        self.aliases
+            .insert(alias.to_lowercase(), language.to_string());
+    }
+
+    /// Fill code lines with the code theme's background color up to the
+    /// full width.
+    pub fn set_background(&mut self, enabled: bool) {
+        self.background = enabled;
+    }
+
+    /// Show a line number gutter. Wrapped continuation lines get no number.
+    ///
+    /// The gutter fits three digits. The length of a streamed block is not
+    /// known up front, so a block running past line 999 widens its gutter
+    /// from then on.
+    pub fn set_line_numbers(&mut self, enabled: bool) {
+        self.line_numbers = enabled;
+    }
+
+    /// Set how lines wider than the terminal are displayed.
+    pub fn set_overflow(&mut self, overflow: CodeOverflow) {
+        self.overflow = overflow;
+    }
+
+    /// Write each code line unchanged on a single output line, so code copied
+    /// from the terminal matches the source.
+    ///
+    /// Leaves out the line number gutter and the padding of background fills,
+    /// and lets the terminal wrap long lines instead of breaking them.
+    pub fn set_copy_safe(&mut self, enabled: bool) {
+        self.copy_safe = enabled;
+    }
+
+    /// Highlight the changed code in diffs, in the syntax inferred from the
+    /// path of the file being patched.
+    pub fn set_diff_highlighting(&mut self, enabled: bool) {
+        self.diff_highlighting = enabled;
+    }
+
+    /// Take the code theme and the gutter, emphasis, diff and shell session
+    /// styles from a theme.
+    pub fn apply_theme(&mut self, theme: &TextTheme) {
+        self.code_theme = theme.code_theme.clone();
+        self.theme_mode = if theme.dark_background {
+            ThemeMode::Dark
+        } else {
+            ThemeMode::Light
+        };
+        self.line_number_style = theme.code_line_number.clone();
+        self.emphasis_style = theme.code_line_emphasis.clone();
+        self.diff_styles = DiffStyles::from_theme(theme);
+        self.prompt_style = theme.shell_prompt.clone();
+        self.output_style = theme.shell_output.clone();
+    }
+
+    /// The syntect theme used for highlighting.
+    fn theme(&self) -> &Theme {
+        select_theme(
+            self.code_theme.as_ref(),
+            &self.assets.theme_set,
+            self.theme_mode,
+        )
+    }
+
+    /// Start a new code block, resetting the highlighting state.
+    ///
+    /// State is kept across the lines of a block so that multi-line constructs
+    /// (block comments, triple-quoted strings, heredocs) highlight correctly.
+    /// The syntax is resolved once here rather than for every line. Blocks
+    /// without a known label are detected from their first non-blank line.
+    pub fn begin_block(&mut self, language: Option<&str>) {
+        let syntax_set = &self.assets.syntax_set;
+        let diff = language
+            .filter(|label| is_diff_label(label))
+            .map(|_| DiffClassifier::default());
+        let session = language
+            .filter(|label| is_session_label(label))
+            .map(|_| SessionClassifier::default());
+
+        // Diffs highlight the patched code, sessions their commands as bash
+        let label = match (&diff, &session) {
+            (Some(_), _) => None,
+            (_, Some(_)) => Some("bash"),
+            _ => language,
+        };
+        let syntax = label.and_then(|label| resolve_syntax(syntax_set, &self.aliases, label));
+        let detect = syntax.is_none() && diff.is_none();
+        let parse = ParseState::new(syntax.unwrap_or_else(|| syntax_set.find_syntax_plain_text()));
+        let highlight = HighlightState::new(&Highlighter::new(self.theme()), ScopeStack::new());
+        self.block = Some(BlockState {
+            parse,
+            highlight,
+            detect,
+            line: 0,
+            number_width: MIN_NUMBER_WIDTH,
+            emphasized: Vec::new(),
+            diff,
+            session,
+        });
+    }
+
+    /// Emphasize lines of the current block, counted from 1.
+    ///
+    /// Lines past the end of the block are ignored.
+    pub fn emphasize_lines(&mut self, lines: &[RangeInclusive<usize>]) {
+        if let Some(block) = &mut self.block {
+            block.emphasized = lines.to_vec();
+        }
+    }
+
+    /// End the current code block, dropping its highlighting state.
+    pub fn end_block(&mut self) {
+        self.block = None;
+    }
+
+    /// Prepare the block state for the next line.
+    ///
+    /// Starts a block if none is open, and detects the syntax of unlabeled
+    /// blocks from their first non-blank line.
+    fn prepare_line(&mut self, line: &str) {
+        if self.block.is_none() {
+            self.begin_block(None);
+        }
+        if !self.block.as_ref().is_some_and(|block| block.detect) || line.trim().is_empty() {
+            return;
+        }
+        let detected = detect_syntax(&self.assets.syntax_set, &self.aliases, line);
+        let is_diff = detected.is_some_and(|syntax| is_diff_label(&syntax.name));
+        // Drop the plain text scopes of any blank lines before this one
+        let highlight = HighlightState::new(&Highlighter::new(self.theme()), ScopeStack::new());
+        if let Some(block) = &mut self.block {
+            block.detect = false;
+            if is_diff {
+                block.diff = Some(DiffClassifier::default());
+            } else if let Some(syntax) = detected {
+                block.parse = ParseState::new(syntax);
+                block.highlight = highlight;
+            }
+        }
+    }
+
+    /// Highlight code in the syntax of a diff's files by their path.
+    fn set_diff_path(&mut self, path: &str) {
+        let path = Path::new(path);
+        let label = path
+            .extension()
+            .or(path.file_name())
+            .and_then(|label| label.to_str());
+        let syntax_set = &self.assets.syntax_set;
+        let syntax = label
+            .and_then(|label| resolve_syntax(syntax_set, &self.aliases, label))
+            .unwrap_or_else(|| syntax_set.find_syntax_plain_text());
+        let highlight = HighlightState::new(&Highlighter::new(self.theme()), ScopeStack::new());
+        if let Some(block) = &mut self.block {
+            block.parse = ParseState::new(syntax);
+            block.highlight = highlight;
+        }
+    }
+
+    /// Highlight a full line of code, returning styled byte ranges of `line`.
+    ///
+    /// The highlighter is cheap to build next to parsing the line, so it is
+    /// built per line rather than kept with the block's state.
+    fn highlight_line(&mut self, line: &str) -> Vec<(Style, Range<usize>)> {
+        let theme = select_theme(
+            self.code_theme.as_ref(),
+            &self.assets.theme_set,
+            self.theme_mode,
+        );
+        let highlighter = Highlighter::new(theme);
+        let Some(block) = &mut self.block else {
+            return Vec::new();
+        };
+
+        // The newline syntax set expects every line to end with `\n`
+        let text = format!("{}\n", line);
+        let Ok(ops) = block.parse.parse_line(&text, &self.assets.syntax_set) else {
+            return vec![(Style::default(), 0..line.len())];
         };
-        let theme = &self.theme_set.themes[theme_name];
-        let mut highlighter = HighlightLines::new(syntax, theme);
 
-        match highlighter.highlight_line(line, &self.syntax_set) {
-            Ok(ranges) => as_24_bit_terminal_escaped(&ranges[..], false),
-            Err(_) => line.to_string(),
+        let mut ranges = Vec::new();
+        let mut pos = 0;
+        let iter = HighlightIterator::new(&mut block.highlight, &ops, &text, &highlighter);
+        for (style, piece) in iter {
+            let end = (pos + piece.len()).min(line.len());
+            if end > pos {
+                ranges.push((style, pos..end));
+            }
+            pos += piece.len();
         }
+        ranges
     }
 
     /// Render a code line with margin, wrapping if needed.
     ///
-    /// Returns multiple lines if the code exceeds the available width.
-    pub fn render_code_line(
-        &self,
+    /// The line is highlighted as a whole before wrapping, so wrapped pieces
+    /// keep the styles of the full line. Returns multiple lines if the code
+    /// exceeds the available width.
+    pub fn render_code_line(&mut self, line: &str, margin: &str, width: usize) -> Vec<String> {
+        self.prepare_line(line);
+        let Some(block) = &mut self.block else {
+            return Vec::new();
+        };
+        block.line += 1;
+        let number = block.line;
+        block.number_width = block.number_width.max(number.to_string().len());
+        if let Some(diff) = &mut block.diff {
+            let kind = diff.classify(line);
+            return self.render_diff_line(line, kind, number, margin, width);
+        }
+        if let Some(session) = &mut block.session {
+            let kind = session.classify(line);
+            return self.render_session_line(line, kind, number, margin, width);
+        }
+        let emphasized = block.emphasized.iter().any(|r| r.contains(&number));
+        let ranges = self.highlight_line(line);
+
+        // Emphasized lines use the emphasis background, others the code theme's
+        let emphasis = emphasized
+            .then_some(self.emphasis_style.bg)
+            .flatten()
+            .map(|color| format!("\x1b[{}m", color.to_bg_str()));
+        let token_background = self.background && emphasis.is_none();
+        let fill = emphasis.or_else(|| self.theme_background());
+
+        let format = LineFormat {
+            margin,
+            number,
+            fill,
+            full_width: true,
+            lead: "",
+            token_background,
+        };
+        self.lay_out(line, Some(&ranges), &format, width)
+    }
+
+    /// Render a line of a diff block.
+    ///
+    /// Added and removed lines get a full-width background, hunk and file
+    /// headers their own styles. The changed code is highlighted in the
+    /// syntax of the file being patched if diff highlighting is enabled.
+    fn render_diff_line(
+        &mut self,
         line: &str,
-        language: Option<&str>,
+        kind: DiffLine<'_>,
+        number: usize,
         margin: &str,
         width: usize,
     ) -> Vec<String> {
-        // Use code_wrap with pretty_broken=true for line wrapping
-        let (indent, wrapped_lines) = code_wrap(line, width, true);
+        if let DiffLine::FileHeader(Some(path)) = kind {
+            if self.diff_highlighting {
+                self.set_diff_path(path);
+            }
+        }
+        let styles = &self.diff_styles;
+        let (text, lead, style, code) = match kind {
+            DiffLine::FileHeader(_) => (line, "", Some(&styles.file_header), false),
+            DiffLine::Hunk => (line, "", Some(&styles.hunk), false),
+            DiffLine::Added(text) => (text, "+", Some(&styles.added), true),
+            DiffLine::Removed(text) => (text, "-", Some(&styles.removed), true),
+            DiffLine::Context(text) => (text, " ", None, true),
+            DiffLine::Other => (line, "", None, false),
+        };
+        let format = LineFormat {
+            margin,
+            number,
+            fill: style.and_then(style_escape),
+            full_width: style.is_some_and(|style| style.bg.is_some()),
+            lead,
+            token_background: false,
+        };
+        let ranges = (code && self.diff_highlighting).then(|| self.highlight_line(text));
+        self.lay_out(text, ranges.as_deref(), &format, width)
+    }
+
+    /// Render a line of a shell session block.
+    ///
+    /// Commands are highlighted as bash after a styled prompt, output lines
+    /// are written in the output style.
+    fn render_session_line(
+        &mut self,
+        line: &str,
+        kind: SessionLine<'_>,
+        number: usize,
+        margin: &str,
+        width: usize,
+    ) -> Vec<String> {
+        match kind {
+            SessionLine::Command {
+                prompt, command, ..
+            } => {
+                let lead = if prompt.is_empty() {
+                    String::new()
+                } else {
+                    self.prompt_style.apply(prompt).to_string()
+                };
+                let ranges = self.highlight_line(command);
+                let format = LineFormat {
+                    margin,
+                    number,
+                    fill: None,
+                    full_width: false,
+                    lead: &lead,
+                    token_background: false,
+                };
+                self.lay_out(command, Some(&ranges), &format, width)
+            }
+            SessionLine::Output => {
+                let format = LineFormat {
+                    margin,
+                    number,
+                    fill: style_escape(&self.output_style),
+                    full_width: false,
+                    lead: "",
+                    token_background: false,
+                };
+                self.lay_out(line, None, &format, width)
+            }
+        }
+    }
+
+    /// The code theme's background as an escape sequence, if background fill
+    /// is enabled.
+    fn theme_background(&self) -> Option<String> {
+        self.background
+            .then(|| self.theme().settings.background)
+            .flatten()
+            .map(|c| format!("\x1b[48;2;{};{};{}m", c.r, c.g, c.b))
+    }
+
+    /// Wrap a line to the width and assemble its output lines.
+    ///
+    /// Pieces are styled with the highlighted `ranges`, or written as-is if
+    /// there are none.
+    fn lay_out(
+        &self,
+        line: &str,
+        ranges: Option<&[(Style, Range<usize>)]>,
+        format: &LineFormat<'_>,
+        width: usize,
+    ) -> Vec<String> {
+        let line_numbers = self.line_numbers && !self.copy_safe;
+        let number_width = self
+            .block
+            .as_ref()
+            .map_or(MIN_NUMBER_WIDTH, |block| block.number_width);
+        let gutter_width = if line_numbers { number_width + 3 } else { 0 };
+        let lead_width = visible_length(format.lead);
+        let width = width.saturating_sub(gutter_width + lead_width);
+
+        // Wrapped lines continue indented a little past the code's own indent
+        let indent = line.len() - line.trim_start().len();
+        let continuation_indent = "  ".repeat(indent.min(4) / 2 + 1);
+
+        // Breaking or cutting lines would change the text copied from them
+        let overflow = if self.copy_safe {
+            CodeOverflow::NoWrap
+        } else {
+            self.overflow
+        };
+        let prefix = match overflow {
+            CodeOverflow::Truncate => truncate_to_width(line, width),
+            CodeOverflow::Wrap | CodeOverflow::NoWrap => None,
+        };
+        let truncated = prefix.is_some();
+        let pieces = match overflow {
+            CodeOverflow::Wrap => {
+                let continuation_width = width.saturating_sub(continuation_indent.len());
+                wrap_ranges(line, width, continuation_width)
+            }
+            CodeOverflow::Truncate | CodeOverflow::NoWrap => {
+                let end = prefix.map_or(line.len(), str::len);
+                std::iter::once(0..end).collect()
+            }
+        };
 
         let mut result = Vec::new();
+        for (i, piece) in pieces.into_iter().enumerate() {
+            let code_line = &line[piece.clone()];
+            let mut highlighted = match ranges {
+                Some(ranges) => escape_span(line, ranges, piece, format.token_background),
+                None => code_line.to_string(),
+            };
+            if truncated {
+                highlighted.push_str(TRUNCATION_MARKER);
+            }
 
-        for (i, code_line) in wrapped_lines.iter().enumerate() {
-            let highlighted = self.highlight_line(code_line, language);
+            let line_indent = if i == 0 { "" } else { &continuation_indent };
 
-            // Add continuation indent for wrapped lines
-            let line_indent = if i == 0 {
-                ""
+            // Only the first piece of a wrapped line is numbered and led
+            let gutter = if !line_numbers {
+                String::new()
+            } else if i == 0 {
+                let label = format!("{:>width$} │ ", format.number, width = number_width);
+                self.line_number_style.apply(&label).to_string()
+            } else {
+                let label = format!("{} │ ", " ".repeat(number_width));
+                self.line_number_style.apply(&label).to_string()
+            };
+            let lead = if i == 0 {
+                format.lead.to_string()
             } else {
-                &"  ".repeat(indent.min(4) / 2 + 1)
+                " ".repeat(lead_width)
             };
 
-            result.push(format!("{}{}{}{}", margin, line_indent, highlighted, RESET));
+            match &format.fill {
+                Some(fill) => {
+                    // Pad with the background color up to the full width, unless
+                    // the trailing spaces would end up in copied code
+                    let pad = if format.full_width && !self.copy_safe {
+                        let used = visible_length(line_indent)
+                            + visible_length(code_line)
+                            + usize::from(truncated);
+                        format!("{}{}", fill, " ".repeat(width.saturating_sub(used)))
+                    } else {
+                        String::new()
+                    };
+                    result.push(format!(
+                        "{}{}{}{}{}{}{}{}",
+                        format.margin, gutter, fill, lead, line_indent, highlighted, pad, RESET
+                    ));
+                }
+                None => {
+                    result.push(format!(
+                        "{}{}{}{}{}{}",
+                        format.margin, gutter, lead, line_indent, highlighted, RESET
+                    ));
+                }
+            }
         }
 
-        if result.is_empty() {
-            result.push(format!("{}{}", margin, RESET));
+        result
+    }
+}
+
+/// Split a line into byte ranges that fit the width, breaking after
+/// whitespace where possible.
+///
+/// The first range fits `width`, the following ones `continuation_width`.
+/// The ranges cover the whole line, so no text is lost or changed.
+fn wrap_ranges(line: &str, width: usize, continuation_width: usize) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    let mut limit = width.max(1);
+    while UnicodeWidthStr::width(&line[start..]) > limit {
+        let mut used = 0;
+        let mut end = start;
+        let mut word_end = None;
+        let mut seen_text = false;
+        for (i, c) in line[start..].char_indices() {
+            used += c.width().unwrap_or(0);
+            if used > limit {
+                break;
+            }
+            end = start + i + c.len_utf8();
+            if !c.is_whitespace() {
+                seen_text = true;
+            } else if seen_text {
+                word_end = Some(end);
+            }
+        }
+        if end == start {
+            // A single character wider than the limit gets a line of its own
+            end += line[start..].chars().next().map_or(0, char::len_utf8);
         }
+        let end = word_end.unwrap_or(end);
+        ranges.push(start..end);
+        start = end;
+        limit = continuation_width.max(1);
+    }
+    if start < line.len() || ranges.is_empty() {
+        ranges.push(start..line.len());
+    }
+    ranges
+}
 
-        result
+/// Cut a line to fit the width, leaving room for the truncation marker.
+///
+/// Returns `None` if the line already fits.
+fn truncate_to_width(line: &str, width: usize) -> Option<&str> {
+    if UnicodeWidthStr::width(line) <= width {
+        return None;
     }
+    let mut used = 0;
+    for (i, c) in line.char_indices() {
+        used += c.width().unwrap_or(0);
+        if used > width.saturating_sub(1) {
+            return Some(&line[..i]);
+        }
+    }
+    Some(line)
+}
+
+/// How to assemble the output lines of one code line.
+struct LineFormat<'a> {
+    /// Left margin of every output line.
+    margin: &'a str,
+    /// Line number shown in the gutter.
+    number: usize,
+    /// Escape sequence applied to the whole line, such as a background.
+    fill: Option<String>,
+    /// Extend the fill to the full width.
+    full_width: bool,
+    /// Text before the code on the first output line, such as a diff sign.
+    lead: &'a str,
+    /// Highlight tokens with the code theme's background.
+    token_background: bool,
+}
+
+/// The configured syntect theme, or the default for the terminal background.
+///
+/// The default falls back to any theme in the set, and to syntect's default
+/// theme if the set is empty.
+fn select_theme<'a>(
+    code_theme: Option<&'a CodeTheme>,
+    theme_set: &'a ThemeSet,
+    mode: ThemeMode,
+) -> &'a Theme {
+    static EMPTY: OnceLock<Theme> = OnceLock::new();
+    if let Some(code_theme) = code_theme {
+        return &code_theme.theme;
+    }
+    let theme_name = match mode {
+        ThemeMode::Dark => "base16-ocean.dark",
+        ThemeMode::Light => "InspiredGitHub",
+    };
+    theme_set
+        .themes
+        .get(theme_name)
+        .or_else(|| theme_set.themes.values().next())
+        .unwrap_or_else(|| EMPTY.get_or_init(Theme::default))
+}
+
+/// Convert the styled ranges overlapping `span` to 24-bit terminal escapes.
+///
+/// With `background`, each piece also sets the theme's background color.
+fn escape_span(
+    line: &str,
+    ranges: &[(Style, Range<usize>)],
+    span: Range<usize>,
+    background: bool,
+) -> String {
+    let pieces: Vec<(Style, &str)> = ranges
+        .iter()
+        .filter_map(|(style, range)| {
+            let start = range.start.max(span.start);
+            let end = range.end.min(span.end);
+            (start < end).then(|| (*style, &line[start..end]))
+        })
+        .collect();
+    as_24_bit_terminal_escaped(&pieces, background)
 }
 
 #[cfg(test)]
 mod tests {
-    use streamdown_render::code::code_wrap;
+    use super::*;
+
+    /// First escape sequence of a highlighted line, i.e. the style of its first token.
+    fn first_style(rendered: &str) -> &str {
+        &rendered[..rendered.find('m').unwrap() + 1]
+    }
+
+    #[test]
+    fn test_block_comment_state_carries_across_lines() {
+        let mut highlighter = CodeHighlighter::default();
+        highlighter.begin_block(Some("rust"));
+        let opening = highlighter.render_code_line("/* start of comment", "", 80);
+        let inside = highlighter.render_code_line("still inside */ let x = 1;", "", 80);
+        assert_eq!(first_style(&inside[0]), first_style(&opening[0]));
+
+        // A fresh block does not treat the same line as a comment
+        highlighter.begin_block(Some("rust"));
+        let fresh = highlighter.render_code_line("still inside */ let x = 1;", "", 80);
+        assert_ne!(first_style(&fresh[0]), first_style(&opening[0]));
+    }
+
+    #[test]
+    fn test_default_code_theme_follows_text_theme() {
+        let mut highlighter = CodeHighlighter::default();
+        highlighter.apply_theme(&TextTheme::light());
+        let assets = HighlightAssets::shared();
+        let light = &assets.theme_set().themes["InspiredGitHub"];
+        assert_eq!(highlighter.theme().name, light.name);
+    }
+
+    #[test]
+    fn test_python_triple_quoted_string() {
+        let mut highlighter = CodeHighlighter::default();
+        highlighter.begin_block(Some("python"));
+        let opening = highlighter.render_code_line("x = \"\"\"", "", 80);
+        let inside = highlighter.render_code_line("def not_code():", "", 80);
+        highlighter.begin_block(Some("python"));
+        let fresh = highlighter.render_code_line("def not_code():", "", 80);
+        assert_ne!(first_style(&inside[0]), first_style(&fresh[0]));
+        assert!(opening[0].contains("x"));
+    }
+
+    #[test]
+    fn test_wrapped_line_keeps_text() {
+        let mut highlighter = CodeHighlighter::default();
+        highlighter.begin_block(Some("rust"));
+        let line = format!("let s = \"{}\";", "a".repeat(60));
+        let rendered = highlighter.render_code_line(&line, "", 30);
+        assert!(rendered.len() > 1);
+        let text: String = rendered
+            .iter()
+            .enumerate()
+            .map(|(i, l)| {
+                let stripped = strip_ansi_escapes::strip_str(l);
+                let indent = if i == 0 { "" } else { "  " };
+                stripped.strip_prefix(indent).unwrap().to_string()
+            })
+            .collect();
+        assert_eq!(text, line);
+        assert!(rendered.iter().all(|l| visible_length(l) <= 30));
+    }
+
+    #[test]
+    fn test_wrapped_line_keeps_colors() {
+        let line = format!("let s = \"{}\";", "a".repeat(60));
+        let mut highlighter = CodeHighlighter::default();
+        highlighter.begin_block(Some("rust"));
+        let full = highlighter.render_code_line(&line, "", 200).remove(0);
+        let string_start = full[..full.find("aaaa").unwrap()].rfind("\x1b[").unwrap();
+        let string_style = first_style(&full[string_start..]);
+
+        highlighter.begin_block(Some("rust"));
+        let wrapped = highlighter.render_code_line(&line, "", 30);
+        assert!(wrapped.len() > 2);
+        for piece in &wrapped[1..] {
+            assert!(piece.contains(&format!("{}a", string_style)), "{:?}", piece);
+        }
+    }
+
+    #[test]
+    fn test_wrap_ranges() {
+        let line = "    call(first, second)";
+        let ranges = wrap_ranges(line, 16, 12);
+        let pieces: Vec<&str> = ranges.iter().map(|r| &line[r.clone()]).collect();
+        assert_eq!(pieces, vec!["    call(first, ", "second)"]);
+
+        let pieces: Vec<&str> = wrap_ranges("日本語日本語", 5, 5)
+            .into_iter()
+            .map(|r| &"日本語日本語"[r])
+            .collect();
+        assert_eq!(pieces, vec!["日本", "語日", "本語"]);
+        assert_eq!(wrap_ranges("", 10, 10), vec![0..0]);
+    }
+
+    #[test]
+    fn test_shared_assets_are_loaded_once() {
+        let first = HighlightAssets::shared();
+        let second = HighlightAssets::shared();
+        assert!(Arc::ptr_eq(&first.syntax_set, &second.syntax_set));
+        assert!(Arc::ptr_eq(&first.theme_set, &second.theme_set));
+    }
+
+    #[test]
+    fn test_custom_assets() {
+        let assets = HighlightAssets::new(SyntaxSet::load_defaults_newlines(), ThemeSet::new());
+        assert!(!Arc::ptr_eq(
+            &assets.syntax_set,
+            &HighlightAssets::shared().syntax_set
+        ));
+        assert!(assets.theme_set().themes.is_empty());
+
+        // Highlighting still works without any themes
+        let mut highlighter = CodeHighlighter::new(assets);
+        highlighter.begin_block(Some("rust"));
+        assert!(highlighter.render_code_line("fn main() {}", "", 80)[0].contains("main"));
+    }
+
+    const TEST_SYNTAX: &str = r#"%YAML 1.2
+---
+name: Testlang
+file_extensions: [testlang]
+scope: source.testlang
+contexts:
+  main:
+    - match: '\bkw\b'
+      scope: keyword.control.testlang
+"#;
+
+    /// Create an empty scratch directory unique to this test process.
+    fn scratch_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_syntax_folder_is_merged_with_defaults() {
+        let dir = scratch_dir("syntax-folder");
+        std::fs::write(dir.join("testlang.sublime-syntax"), TEST_SYNTAX).unwrap();
+
+        let assets = HighlightAssets::shared().with_syntax_folder(&dir).unwrap();
+        assert!(assets
+            .syntax_set()
+            .find_syntax_by_token("testlang")
+            .is_some());
+        assert!(assets.syntax_set().find_syntax_by_token("rust").is_some());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_syntax_dump_is_merged_with_defaults() {
+        let dir = scratch_dir("syntax-dump");
+        std::fs::write(dir.join("testlang.sublime-syntax"), TEST_SYNTAX).unwrap();
+        let mut builder = syntect::parsing::SyntaxSetBuilder::new();
+        builder.add_from_folder(&dir, true).unwrap();
+        let dump = dir.join("extra.packdump");
+        syntect::dumps::dump_to_file(&builder.build(), &dump).unwrap();
+
+        let assets = HighlightAssets::shared().with_syntax_dump(&dump).unwrap();
+        assert!(assets
+            .syntax_set()
+            .find_syntax_by_token("testlang")
+            .is_some());
+        assert!(assets.syntax_set().find_syntax_by_token("python").is_some());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_missing_syntax_dump() {
+        let err = HighlightAssets::shared()
+            .with_syntax_dump("/nonexistent/extra.packdump")
+            .unwrap_err();
+        assert!(matches!(err, Error::SyntaxLoad { .. }));
+    }
+
+    fn with_code_theme(name: &str) -> CodeHighlighter {
+        let mut theme = TextTheme::dark();
+        theme.code_theme = Some(CodeTheme::named(name).unwrap());
+        let mut highlighter = CodeHighlighter::default();
+        highlighter.apply_theme(&theme);
+        highlighter
+    }
+
+    #[test]
+    fn test_named_code_theme() {
+        let theme = CodeTheme::named("InspiredGitHub").unwrap();
+        assert_eq!(theme.name(), "InspiredGitHub");
+    }
+
+    #[test]
+    fn test_unknown_code_theme_lists_available() {
+        let err = CodeTheme::named("no-such-theme").unwrap_err();
+        match &err {
+            Error::UnknownCodeTheme { name, available } => {
+                assert_eq!(name, "no-such-theme");
+                assert!(available.iter().any(|n| n == "base16-ocean.dark"));
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+        assert!(err.to_string().contains("InspiredGitHub"));
+    }
+
+    #[test]
+    fn test_missing_code_theme_file() {
+        let err = CodeTheme::from_file("/nonexistent/theme.tmTheme").unwrap_err();
+        assert!(matches!(err, Error::CodeThemeLoad { .. }));
+    }
+
+    #[test]
+    fn test_code_theme_changes_colors() {
+        let render = |code_theme: &str| {
+            let mut highlighter = with_code_theme(code_theme);
+            highlighter.begin_block(Some("rust"));
+            highlighter
+                .render_code_line("fn main() {}", "", 80)
+                .remove(0)
+        };
+        assert_ne!(render("InspiredGitHub"), render("Solarized (dark)"));
+    }
+
+    #[test]
+    fn test_unlabeled_block_is_detected() {
+        let mut detected = CodeHighlighter::default();
+        detected.begin_block(None);
+        detected.render_code_line("", "", 80);
+        detected.render_code_line("#!/usr/bin/env python3", "", 80);
+        let line = detected.render_code_line("def main(): pass", "", 80);
+
+        let mut labeled = CodeHighlighter::default();
+        labeled.begin_block(Some("python"));
+        labeled.render_code_line("#!/usr/bin/env python3", "", 80);
+        assert_eq!(line, labeled.render_code_line("def main(): pass", "", 80));
+    }
+
+    #[test]
+    fn test_detected_block_after_blank_line_has_no_plain_text_scope() {
+        let mut highlighter = CodeHighlighter::default();
+        highlighter.begin_block(None);
+        highlighter.render_code_line("", "", 80);
+        highlighter.render_code_line("fn main() {", "", 80);
+        let block = highlighter.block.as_ref().unwrap();
+        let scopes = block.highlight.path.as_slice();
+        assert_eq!(scopes[0].build_string(), "source.rust");
+    }
+
+    #[test]
+    fn test_custom_language_alias() {
+        let mut highlighter = CodeHighlighter::default();
+        highlighter.add_language_alias("Starlark", "python");
+        highlighter.begin_block(Some("starlark"));
+        let aliased = highlighter.render_code_line("def main(): pass", "", 80);
+        highlighter.begin_block(Some("python"));
+        assert_eq!(
+            aliased,
+            highlighter.render_code_line("def main(): pass", "", 80)
+        );
+    }
+
+    #[test]
+    fn test_background_fills_width() {
+        let mut highlighter = with_code_theme("base16-ocean.dark");
+        highlighter.set_background(true);
+        highlighter.begin_block(Some("rust"));
+        for line in ["fn main() {}", ""] {
+            let rendered = highlighter.render_code_line(line, "  ", 40).remove(0);
+            assert!(rendered.starts_with("  \x1b[48;2;43;48;59m"));
+            assert!(rendered.ends_with(&format!("{}{}", " ".repeat(40 - line.len()), RESET)));
+        }
+    }
+
+    #[test]
+    fn test_line_numbers() {
+        let mut highlighter = CodeHighlighter::default();
+        highlighter.set_line_numbers(true);
+        highlighter.begin_block(None);
+        let first = highlighter.render_code_line("first", "", 40);
+        let second = highlighter.render_code_line("second", "", 40);
+        let strip = |line: &str| strip_ansi_escapes::strip_str(line);
+        assert_eq!(strip(&first[0]), "  1 │ first");
+        assert_eq!(strip(&second[0]), "  2 │ second");
+    }
+
+    #[test]
+    fn test_gutter_width_is_kept_for_the_block() {
+        let mut highlighter = CodeHighlighter::default();
+        highlighter.set_line_numbers(true);
+        highlighter.begin_block(None);
+        for _ in 1..1000 {
+            highlighter.render_code_line("line", "", 40);
+        }
+        let strip = |line: &str| strip_ansi_escapes::strip_str(line);
+        assert_eq!(
+            strip(&highlighter.render_code_line("line", "", 40)[0]),
+            "1000 │ line"
+        );
+        let wrapped = highlighter.render_code_line(&"word ".repeat(10), "", 30);
+        assert!(strip(&wrapped[1]).starts_with("     │ "));
+    }
+
+    #[test]
+    fn test_emphasis_past_the_block_keeps_the_gutter() {
+        let mut highlighter = CodeHighlighter::default();
+        highlighter.set_line_numbers(true);
+        highlighter.begin_block(None);
+        highlighter.emphasize_lines(&[1..=999_999_999]);
+        let first = highlighter.render_code_line("first", "", 40);
+        assert_eq!(strip_ansi_escapes::strip_str(&first[0]), "  1 │ first");
+    }
+
+    #[test]
+    fn test_wrapped_lines_have_no_number() {
+        let mut highlighter = CodeHighlighter::default();
+        highlighter.set_line_numbers(true);
+        highlighter.begin_block(None);
+        let rendered = highlighter.render_code_line(&"word ".repeat(10), "", 30);
+        assert!(rendered.len() > 1);
+        let strip = |line: &str| strip_ansi_escapes::strip_str(line);
+        assert!(strip(&rendered[0]).starts_with("  1 │ word"));
+        assert!(strip(&rendered[1]).starts_with("    │ "));
+    }
+
+    #[test]
+    fn test_emphasized_lines_fill_background() {
+        let mut highlighter = CodeHighlighter::default();
+        highlighter.apply_theme(&TextTheme::dark());
+        highlighter.begin_block(Some("rust"));
+        highlighter.emphasize_lines(&[2..=2]);
+        let plain = highlighter.render_code_line("let a = 1;", "", 20).remove(0);
+        let emphasized = highlighter.render_code_line("let b = 2;", "", 20).remove(0);
+        let color = TextTheme::dark().code_line_emphasis.bg.unwrap();
+        let band = format!("\x1b[{}m", color.to_bg_str());
+        assert!(!plain.contains(&band));
+        assert!(emphasized.starts_with(&band));
+        assert!(emphasized.ends_with(&format!("{}{}", " ".repeat(10), RESET)));
+    }
+
+    fn diff_highlighter(highlighting: bool) -> CodeHighlighter {
+        let mut highlighter = CodeHighlighter::default();
+        highlighter.apply_theme(&TextTheme::dark());
+        highlighter.set_diff_highlighting(highlighting);
+        highlighter.begin_block(Some("diff"));
+        highlighter
+    }
+
+    #[test]
+    fn test_diff_lines_fill_width() {
+        let mut highlighter = diff_highlighter(false);
+        let theme = TextTheme::dark();
+        let added = highlighter
+            .render_code_line("+let x = 1;", "", 30)
+            .remove(0);
+        let removed = highlighter
+            .render_code_line("-let x = 2;", "", 30)
+            .remove(0);
+        let context = highlighter
+            .render_code_line(" let y = 3;", "", 30)
+            .remove(0);
+
+        let added_style = style_escape(&theme.diff_added).unwrap();
+        assert!(added.starts_with(&format!("{}+let x = 1;", added_style)));
+        assert!(added.ends_with(&format!("{}{}", " ".repeat(19), RESET)));
+        assert!(removed.starts_with(&style_escape(&theme.diff_removed).unwrap()));
+        assert_eq!(context, format!(" let y = 3;{}", RESET));
+    }
+
+    #[test]
+    fn test_diff_highlights_code_by_path() {
+        let mut highlighter = diff_highlighter(true);
+        highlighter.render_code_line("--- a/src/main.rs", "", 40);
+        highlighter.render_code_line("+++ b/src/main.rs", "", 40);
+        highlighter.render_code_line("@@ -1 +1 @@", "", 40);
+        let added = highlighter
+            .render_code_line("+fn main() {}", "", 40)
+            .remove(0);
+
+        let mut rust = CodeHighlighter::default();
+        rust.begin_block(Some("rust"));
+        let code = rust.render_code_line("fn main() {}", "", 40).remove(0);
+        let code = code.strip_suffix(RESET).unwrap();
+        assert!(added.contains(code));
+    }
+
+    #[test]
+    fn test_unlabeled_diff_is_detected() {
+        let mut highlighter = CodeHighlighter::default();
+        highlighter.apply_theme(&TextTheme::dark());
+        highlighter.begin_block(None);
+        highlighter.render_code_line("diff --git a/x.py b/x.py", "", 30);
+        let added = highlighter.render_code_line("+pass", "", 30).remove(0);
+        let added_style = style_escape(&TextTheme::dark().diff_added).unwrap();
+        assert!(added.starts_with(&added_style));
+    }
+
+    #[test]
+    fn test_shell_session() {
+        let mut highlighter = CodeHighlighter::default();
+        highlighter.apply_theme(&TextTheme::dark());
+        highlighter.begin_block(Some("console"));
+        let command = highlighter
+            .render_code_line("$ echo \"hi\"", "", 40)
+            .remove(0);
+        let output = highlighter.render_code_line("hi", "", 40).remove(0);
+
+        let mut bash = CodeHighlighter::default();
+        bash.begin_block(Some("bash"));
+        let code = bash.render_code_line("echo \"hi\"", "", 40).remove(0);
+        assert!(command.contains(code.strip_suffix(RESET).unwrap()));
+        assert_eq!(strip_ansi_escapes::strip_str(&command), "$ echo \"hi\"");
+
+        let output_style = style_escape(&TextTheme::dark().shell_output).unwrap();
+        assert_eq!(output, format!("{}hi{}", output_style, RESET));
+    }
+
+    fn render_with_overflow(overflow: CodeOverflow, line: &str, width: usize) -> Vec<String> {
+        let mut highlighter = CodeHighlighter::default();
+        highlighter.set_overflow(overflow);
+        highlighter.begin_block(Some("bash"));
+        highlighter
+            .render_code_line(line, "", width)
+            .iter()
+            .map(strip_ansi_escapes::strip_str)
+            .collect()
+    }
+
+    #[test]
+    fn test_truncate_overflow() {
+        let line = "curl -X POST https://example.com/api/v1/items";
+        assert_eq!(
+            render_with_overflow(CodeOverflow::Truncate, line, 20),
+            vec!["curl -X POST https:…"]
+        );
+        assert_eq!(
+            render_with_overflow(CodeOverflow::Truncate, "echo 日本語", 10),
+            vec!["echo 日本…"]
+        );
+        assert_eq!(
+            render_with_overflow(CodeOverflow::Truncate, "ls", 20),
+            vec!["ls"]
+        );
+    }
+
+    #[test]
+    fn test_no_wrap_overflow_keeps_source() {
+        let line = "curl -X POST https://example.com/api/v1/items";
+        assert_eq!(
+            render_with_overflow(CodeOverflow::NoWrap, line, 20),
+            vec![line]
+        );
+    }
+
+    #[test]
+    fn test_copy_safe_line_matches_source() {
+        let mut highlighter = CodeHighlighter::default();
+        highlighter.set_line_numbers(true);
+        highlighter.set_background(true);
+        highlighter.set_copy_safe(true);
+        highlighter.begin_block(Some("bash"));
+        highlighter.emphasize_lines(&[2..=2]);
+        let line = "    echo one two three four";
+        for _ in 0..2 {
+            let rendered: Vec<String> = highlighter
+                .render_code_line(line, "", 16)
+                .iter()
+                .map(strip_ansi_escapes::strip_str)
+                .collect();
+            assert_eq!(rendered, vec![line]);
+        }
+    }
+
+    #[test]
+    fn test_empty_line() {
+        let mut highlighter = CodeHighlighter::default();
+        highlighter.begin_block(None);
+        let rendered = highlighter.render_code_line("", "  ", 80);
+        assert_eq!(rendered, vec![format!("  {}", RESET)]);
+    }
+
+    /// Wrap a line, checking that the pieces cover it and fit their widths.
+    fn wrap(line: &str, width: usize, continuation_width: usize) -> Vec<&str> {
+        let pieces: Vec<&str> = wrap_ranges(line, width, continuation_width)
+            .into_iter()
+            .map(|range| &line[range])
+            .collect();
+        assert_eq!(pieces.concat(), line);
+        for (i, piece) in pieces.iter().enumerate() {
+            let limit = if i == 0 { width } else { continuation_width };
+            assert!(
+                piece.chars().count() == 1 || piece.width() <= limit,
+                "{:?}",
+                piece
+            );
+        }
+        pieces
+    }
 
     #[test]
     fn test_code_wrap_short_line() {
-        let (indent, lines) = code_wrap("let x = 1;", 80, true);
-        assert_eq!(indent, 0);
-        assert_eq!(lines.len(), 1);
-        assert_eq!(lines[0], "let x = 1;");
+        assert_eq!(wrap("let x = 1;", 80, 76), vec!["let x = 1;"]);
     }
 
     #[test]
     fn test_code_wrap_with_indent() {
-        let (indent, lines) = code_wrap("    let x = 1;", 80, true);
-        assert_eq!(indent, 4);
-        assert_eq!(lines.len(), 1);
+        assert_eq!(wrap("    let x = 1;", 80, 76), vec!["    let x = 1;"]);
     }
 
     #[test]
     fn test_code_wrap_long_line() {
         let long_line = "x".repeat(100);
-        let (_, lines) = code_wrap(&long_line, 40, true);
-        assert!(lines.len() > 1);
+        let lengths: Vec<usize> = wrap(&long_line, 40, 36).iter().map(|p| p.len()).collect();
+        assert_eq!(lengths, vec![40, 36, 24]);
     }
 
     #[test]
     fn test_code_wrap_empty() {
-        let (indent, lines) = code_wrap("", 80, true);
-        assert_eq!(indent, 0);
-        assert_eq!(lines.len(), 1);
+        assert_eq!(wrap("", 80, 76), vec![""]);
+    }
+
+    #[test]
+    fn test_code_wrap_multibyte_at_boundary() {
+        assert_eq!(wrap("abcdé fgh", 5, 5), vec!["abcdé", " fgh"]);
+        assert_eq!(wrap("ééééé", 2, 2), vec!["éé", "éé", "é"]);
+    }
+
+    #[test]
+    fn test_code_wrap_wide_at_boundary() {
+        // A wide character that would cross the limit starts the next piece
+        assert_eq!(wrap("ab日本", 3, 3), vec!["ab", "日", "本"]);
+        // One wider than the limit gets a piece of its own
+        assert_eq!(wrap("日本", 1, 1), vec!["日", "本"]);
     }
 }
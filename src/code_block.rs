@@ -0,0 +1,231 @@
+//! Metadata for code blocks extracted from the rendered output.
+
+use std::ops::{Range, RangeInclusive};
+
+/// A completed code block, exactly as the model produced it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CodeBlock {
+    /// Position of the block within its message, starting at 0.
+    pub index: usize,
+    /// Language from the fence info string, if any.
+    pub language: Option<String>,
+    /// Title or file path from the fence info string, as in
+    /// ```` ```rust:src/main.rs ```` or ```` ```python title="app.py" ````.
+    pub title: Option<String>,
+    /// Full fence info string, empty if the fence had none.
+    pub info: String,
+    /// Lines to emphasize, from an info string annotation like `{3,7-9}`.
+    /// Lines are counted from 1.
+    pub emphasized_lines: Vec<RangeInclusive<usize>>,
+    /// Source code, with lines joined by `\n`.
+    pub source: String,
+    /// Commands of a shell session block (```` ```console ````), without
+    /// their prompts. Empty for other blocks.
+    pub commands: Vec<String>,
+    /// Input lines holding the code (fences excluded), counted from 0 at the
+    /// start of the message.
+    pub lines: Range<usize>,
+}
+
+impl CodeBlock {
+    /// Create a code block from its fence info string.
+    ///
+    /// `info` is the `language` of the parser's `CodeBlockStart` event. This
+    /// assumes the parser passes the whole info string after the opening
+    /// fence there. If it only passed the first word, blocks would keep their
+    /// language but lose titles given as attributes (`title="app.py"`) and
+    /// line annotations separated by a space (`rust {3}`).
+    pub(crate) fn new(index: usize, info: Option<&str>, first_line: usize) -> Self {
+        let info = info.unwrap_or_default().trim().to_string();
+        let fence = FenceInfo::parse(&info);
+        Self {
+            index,
+            language: fence.language,
+            title: fence.title,
+            emphasized_lines: fence.emphasized_lines,
+            info,
+            source: String::new(),
+            commands: Vec::new(),
+            lines: first_line..first_line,
+        }
+    }
+}
+
+/// Attribute names that give a code block's title.
+const TITLE_ATTRIBUTES: &[&str] = &["title", "filename", "file", "path"];
+
+/// A fence info string split into its parts.
+///
+/// Understands the forms coding models commonly emit:
+///
+/// - ```` ```rust ````
+/// - ```` ```rust:src/main.rs ````
+/// - ```` ```python title="app.py" ```` (also `filename`, `file` and `path`)
+/// - ```` ```rust {3,7-9} ```` or ```` ```rust{3,7-9} ````
+#[derive(Debug, Default, PartialEq, Eq)]
+struct FenceInfo {
+    language: Option<String>,
+    title: Option<String>,
+    emphasized_lines: Vec<RangeInclusive<usize>>,
+}
+
+impl FenceInfo {
+    fn parse(info: &str) -> Self {
+        let mut fence = Self::default();
+        for (i, word) in split_words(info).into_iter().enumerate() {
+            if let Some((key, value)) = word.split_once('=') {
+                if TITLE_ATTRIBUTES.contains(&key.to_lowercase().as_str()) {
+                    fence.title = Some(unquote(value).to_string()).filter(|t| !t.is_empty());
+                }
+                continue;
+            }
+
+            // Line annotations may be attached to the language: `rust{3}`
+            let (word, lines) = match word.find('{') {
+                Some(start) => (&word[..start], &word[start..]),
+                None => (word, ""),
+            };
+            if !lines.is_empty() {
+                fence.emphasized_lines = parse_line_ranges(lines);
+            }
+            if i == 0 && !word.is_empty() {
+                let (language, path) = match word.split_once(':') {
+                    Some((language, path)) => (language, Some(path)),
+                    None => (word, None),
+                };
+                fence.language = Some(language.to_string()).filter(|l| !l.is_empty());
+                if let Some(path) = path.filter(|path| !path.is_empty()) {
+                    fence.title = Some(path.to_string());
+                }
+            }
+        }
+        fence
+    }
+}
+
+/// Split an info string at whitespace outside of quotes and braces.
+fn split_words(info: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    let mut start = None;
+    let mut quote = None;
+    let mut in_braces = false;
+    for (i, c) in info.char_indices() {
+        match (c, quote) {
+            ('"' | '\'', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            ('{', None) => in_braces = true,
+            ('}', None) => in_braces = false,
+            (c, None) if c.is_whitespace() && !in_braces => {
+                if let Some(start) = start.take() {
+                    words.push(&info[start..i]);
+                }
+                continue;
+            }
+            _ => {}
+        }
+        start.get_or_insert(i);
+    }
+    if let Some(start) = start {
+        words.push(&info[start..]);
+    }
+    words
+}
+
+/// Strip one pair of matching quotes from an attribute value.
+fn unquote(value: &str) -> &str {
+    ['"', '\'']
+        .iter()
+        .find_map(|&q| value.strip_prefix(q)?.strip_suffix(q))
+        .unwrap_or(value)
+}
+
+/// Parse a line annotation such as `{3,7-9}`.
+///
+/// Malformed entries are skipped. Reversed ranges such as `9-7` are read
+/// as `7-9`.
+fn parse_line_ranges(annotation: &str) -> Vec<RangeInclusive<usize>> {
+    let inner = annotation.trim_start_matches('{');
+    let inner = inner.split('}').next().unwrap_or_default();
+    inner
+        .split(',')
+        .filter_map(|part| match part.split_once('-') {
+            Some((first, last)) => {
+                let first: usize = first.trim().parse().ok()?;
+                let last: usize = last.trim().parse().ok()?;
+                Some(first.min(last)..=first.max(last))
+            }
+            None => {
+                let line = part.trim().parse().ok()?;
+                Some(line..=line)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_language_is_first_word_of_info() {
+        let block = CodeBlock::new(0, Some("rust ignore"), 3);
+        assert_eq!(block.language.as_deref(), Some("rust"));
+        assert_eq!(block.info, "rust ignore");
+        assert_eq!(block.lines, 3..3);
+    }
+
+    #[test]
+    fn test_no_info_string() {
+        let block = CodeBlock::new(1, None, 0);
+        assert_eq!(block.language, None);
+        assert_eq!(block.info, "");
+        assert!(block.emphasized_lines.is_empty());
+    }
+
+    #[test]
+    fn test_emphasized_lines() {
+        let block = CodeBlock::new(0, Some("rust {3,7-9}"), 0);
+        assert_eq!(block.language.as_deref(), Some("rust"));
+        assert_eq!(block.emphasized_lines, vec![3..=3, 7..=9]);
+
+        let block = CodeBlock::new(0, Some("python{1, x, 4 - 5}"), 0);
+        assert_eq!(block.language.as_deref(), Some("python"));
+        assert_eq!(block.emphasized_lines, vec![1..=1, 4..=5]);
+
+        let block = CodeBlock::new(0, Some("rust {9-7}"), 0);
+        assert_eq!(block.emphasized_lines, vec![7..=9]);
+    }
+
+    #[test]
+    fn test_language_with_path() {
+        let block = CodeBlock::new(0, Some("rust:src/main.rs"), 0);
+        assert_eq!(block.language.as_deref(), Some("rust"));
+        assert_eq!(block.title.as_deref(), Some("src/main.rs"));
+    }
+
+    #[test]
+    fn test_title_attribute() {
+        let block = CodeBlock::new(0, Some(r#"python title="my app.py" {2}"#), 0);
+        assert_eq!(block.language.as_deref(), Some("python"));
+        assert_eq!(block.title.as_deref(), Some("my app.py"));
+        assert_eq!(block.emphasized_lines, vec![2..=2]);
+
+        let block = CodeBlock::new(0, Some("js filename='index.js'"), 0);
+        assert_eq!(block.title.as_deref(), Some("index.js"));
+    }
+
+    #[test]
+    fn test_title_without_language() {
+        let block = CodeBlock::new(0, Some(r#"title="{weird}.txt""#), 0);
+        assert_eq!(block.language, None);
+        assert_eq!(block.title.as_deref(), Some("{weird}.txt"));
+        assert!(block.emphasized_lines.is_empty());
+    }
+
+    #[test]
+    fn test_annotation_without_language() {
+        let block = CodeBlock::new(0, Some("{2}"), 0);
+        assert_eq!(block.language, None);
+        assert_eq!(block.emphasized_lines, vec![2..=2]);
+    }
+}
@@ -0,0 +1,227 @@
+//! Layout and styling configuration for the renderer.
+
+use crate::code::{CodeBlockStyle, CodeCollapse, CodeOverflow, CodeTheme, HighlightAssets};
+use crate::flush::FlushPolicy;
+use crate::theme::Theme;
+use crate::utils::terminal_width;
+
+/// Width used when no terminal width can be detected.
+const FALLBACK_WIDTH: usize = 80;
+
+/// Configuration for [`StreamdownRenderer`](crate::StreamdownRenderer).
+///
+/// # Example
+///
+/// ```no_run
+/// use forge_markdown_stream::{RendererConfig, StreamdownRenderer, Theme};
+/// use std::io;
+///
+/// let config = RendererConfig::new().max_width(100).padding(2).theme(Theme::dark());
+/// let renderer = StreamdownRenderer::from_config(io::stdout(), config);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct RendererConfig {
+    pub(crate) width: Option<usize>,
+    pub(crate) max_width: Option<usize>,
+    pub(crate) padding: usize,
+    pub(crate) theme: Option<Theme>,
+    pub(crate) live_preview: bool,
+    pub(crate) flush_policy: FlushPolicy,
+    pub(crate) highlight_assets: Option<HighlightAssets>,
+    pub(crate) code_theme: Option<CodeTheme>,
+    pub(crate) language_aliases: Vec<(String, String)>,
+    pub(crate) code_block_style: CodeBlockStyle,
+    pub(crate) code_background: bool,
+    pub(crate) code_line_numbers: bool,
+    pub(crate) diff_highlighting: bool,
+    pub(crate) code_collapse: Option<CodeCollapse>,
+    pub(crate) code_overflow: CodeOverflow,
+    pub(crate) copy_safe_code: bool,
+    pub(crate) code_clipboard: bool,
+}
+
+impl RendererConfig {
+    /// Create a configuration that detects the width and theme from the terminal.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use a fixed terminal width instead of detecting it.
+    pub fn width(mut self, width: usize) -> Self {
+        self.width = Some(width);
+        self
+    }
+
+    /// Clamp the content width to a maximum readable width.
+    pub fn max_width(mut self, max_width: usize) -> Self {
+        self.max_width = Some(max_width);
+        self
+    }
+
+    /// Indent all output by the given number of columns.
+    pub fn padding(mut self, padding: usize) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Use a specific theme instead of detecting one from the terminal.
+    pub fn theme(mut self, theme: Theme) -> Self {
+        self.theme = Some(theme);
+        self
+    }
+
+    /// Enable live preview of the in-progress line.
+    pub fn live_preview(mut self, enabled: bool) -> Self {
+        self.live_preview = enabled;
+        self
+    }
+
+    /// Set when the writer is flushed.
+    pub fn flush_policy(mut self, policy: FlushPolicy) -> Self {
+        self.flush_policy = policy;
+        self
+    }
+
+    /// Use specific syntax and theme sets for code highlighting.
+    ///
+    /// By default all renderers share the process-wide
+    /// [`HighlightAssets::shared`] sets.
+    pub fn highlight_assets(mut self, assets: HighlightAssets) -> Self {
+        self.highlight_assets = Some(assets);
+        self
+    }
+
+    /// Highlight code blocks with a specific syntect theme.
+    ///
+    /// Overrides [`Theme::code_theme`] of the configured theme.
+    pub fn code_theme(mut self, code_theme: CodeTheme) -> Self {
+        self.code_theme = Some(code_theme);
+        self
+    }
+
+    /// Highlight code fences labeled `alias` as `language`.
+    ///
+    /// Common LLM labels such as `tsx`, `shell` or `golang` are built in;
+    /// aliases only apply to labels syntect does not know.
+    pub fn language_alias(mut self, alias: &str, language: &str) -> Self {
+        self.language_aliases
+            .push((alias.to_string(), language.to_string()));
+        self
+    }
+
+    /// Set how code blocks are set off from the surrounding text.
+    pub fn code_block_style(mut self, style: CodeBlockStyle) -> Self {
+        self.code_block_style = style;
+        self
+    }
+
+    /// Fill code blocks with the code theme's background color.
+    pub fn code_background(mut self, enabled: bool) -> Self {
+        self.code_background = enabled;
+        self
+    }
+
+    /// Show line numbers in code blocks.
+    ///
+    /// Lines listed in a fence annotation like ```` ```rust {3,7-9} ```` are
+    /// emphasized whether or not numbers are shown.
+    pub fn code_line_numbers(mut self, enabled: bool) -> Self {
+        self.code_line_numbers = enabled;
+        self
+    }
+
+    /// Highlight the changed code in `diff` and `patch` blocks, in the
+    /// syntax inferred from the path of the file being patched.
+    pub fn diff_highlighting(mut self, enabled: bool) -> Self {
+        self.diff_highlighting = enabled;
+        self
+    }
+
+    /// Collapse long code blocks to their first `head` and last `tail` lines.
+    pub fn code_collapse(mut self, head: usize, tail: usize) -> Self {
+        self.code_collapse = Some(CodeCollapse::new(head, tail));
+        self
+    }
+
+    /// Set how code lines wider than the terminal are displayed.
+    pub fn code_overflow(mut self, overflow: CodeOverflow) -> Self {
+        self.code_overflow = overflow;
+        self
+    }
+
+    /// Render each code line unchanged on a single output line, so code
+    /// copied from the terminal matches the source.
+    ///
+    /// Code lines get no blockquote margin, padding, line numbers or
+    /// background padding, and long lines are left to the terminal to wrap
+    /// whatever the [`code_overflow`](Self::code_overflow) setting.
+    pub fn copy_safe_code(mut self, enabled: bool) -> Self {
+        self.copy_safe_code = enabled;
+        self
+    }
+
+    /// Copy each completed code block to the system clipboard with an
+    /// OSC 52 escape sequence.
+    ///
+    /// Only terminals that support OSC 52 act on it; others ignore it.
+    pub fn code_clipboard(mut self, enabled: bool) -> Self {
+        self.code_clipboard = enabled;
+        self
+    }
+
+    /// Resolve the terminal width.
+    ///
+    /// Uses the configured width if set, otherwise the width of the terminal
+    /// attached to stdout, then the `COLUMNS` environment variable, and
+    /// finally 80 columns.
+    pub fn resolved_width(&self) -> usize {
+        self.width
+            .or_else(terminal_width)
+            .or_else(columns_from_env)
+            .unwrap_or(FALLBACK_WIDTH)
+    }
+}
+
+/// Read the terminal width from the `COLUMNS` environment variable.
+fn columns_from_env() -> Option<usize> {
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+        .filter(|&columns| columns > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_explicit_width_wins() {
+        assert_eq!(RendererConfig::new().width(120).resolved_width(), 120);
+    }
+
+    #[test]
+    fn test_builder_sets_fields() {
+        let config = RendererConfig::new()
+            .width(100)
+            .max_width(80)
+            .padding(2)
+            .live_preview(true);
+        assert_eq!(config.width, Some(100));
+        assert_eq!(config.max_width, Some(80));
+        assert_eq!(config.padding, 2);
+        assert!(config.live_preview);
+        assert!(config.theme.is_none());
+    }
+
+    #[test]
+    fn test_padding_applies_to_paragraphs() {
+        let config = RendererConfig::new().width(40).padding(4);
+        let mut renderer = crate::StreamdownRenderer::from_config(Vec::new(), config);
+        renderer.push("Some **plain** prose\n\nAnother paragraph\n").unwrap();
+        let output = String::from_utf8(renderer.finish().unwrap()).unwrap();
+        let output = strip_ansi_escapes::strip_str(output);
+
+        let lines: Vec<&str> = output.lines().filter(|line| !line.is_empty()).collect();
+        assert_eq!(lines, vec!["    Some plain prose", "    Another paragraph"]);
+    }
+}
@@ -0,0 +1,288 @@
+//! Classification of unified diff and `apply_patch` lines.
+//!
+//! Diff blocks are rendered line by line as they stream in, so the
+//! classifier keeps just enough state to tell hunk content from file headers:
+//! a removed line `-- comment` appears as `--- comment` inside a hunk.
+
+use crate::theme::{Style, Theme};
+
+/// Fence labels rendered as diffs.
+const DIFF_LABELS: &[&str] = &["diff", "patch", "udiff"];
+
+/// Metadata lines of `git diff` output shown as file headers.
+const METADATA_PREFIXES: &[&str] = &[
+    "index ",
+    "new file mode ",
+    "deleted file mode ",
+    "old mode ",
+    "new mode ",
+    "similarity index ",
+    "rename from ",
+    "rename to ",
+];
+
+/// Check if a fence label names a diff.
+pub(crate) fn is_diff_label(label: &str) -> bool {
+    DIFF_LABELS
+        .iter()
+        .any(|diff| diff.eq_ignore_ascii_case(label))
+}
+
+/// The kind of a diff line.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum DiffLine<'a> {
+    /// A file header or metadata line, with the path of the new file if the
+    /// line names one.
+    FileHeader(Option<&'a str>),
+    /// A hunk header such as `@@ -1,3 +1,4 @@`.
+    Hunk,
+    /// An added line, without its `+`.
+    Added(&'a str),
+    /// A removed line, without its `-`.
+    Removed(&'a str),
+    /// An unchanged context line, without its leading space.
+    Context(&'a str),
+    /// Anything else, such as `\ No newline at end of file`.
+    Other,
+}
+
+/// Position within a diff.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum HunkState {
+    /// Outside of any hunk.
+    #[default]
+    Outside,
+    /// In a hunk with line counts, with the old and new lines still to come.
+    Counted { old: usize, new: usize },
+    /// In a hunk without line counts, as in `apply_patch` hunks.
+    Open,
+}
+
+/// Classifies the lines of a diff as they arrive.
+#[derive(Debug, Default)]
+pub(crate) struct DiffClassifier {
+    hunk: HunkState,
+}
+
+impl DiffClassifier {
+    /// Classify the next line of the diff.
+    pub(crate) fn classify<'a>(&mut self, line: &'a str) -> DiffLine<'a> {
+        match self.hunk {
+            HunkState::Counted { old, new } if old > 0 || new > 0 => {
+                let (kind, old, new) = match line.chars().next() {
+                    Some('+') => (DiffLine::Added(&line[1..]), old, new.saturating_sub(1)),
+                    Some('-') => (DiffLine::Removed(&line[1..]), old.saturating_sub(1), new),
+                    Some(' ') => (
+                        DiffLine::Context(&line[1..]),
+                        old.saturating_sub(1),
+                        new.saturating_sub(1),
+                    ),
+                    None => (
+                        DiffLine::Context(""),
+                        old.saturating_sub(1),
+                        new.saturating_sub(1),
+                    ),
+                    Some(_) => (DiffLine::Other, old, new),
+                };
+                self.hunk = HunkState::Counted { old, new };
+                return kind;
+            }
+            HunkState::Open if !line.starts_with("***") && !line.starts_with("diff ") => {}
+            _ => {
+                if let Some(kind) = self.classify_header(line) {
+                    return kind;
+                }
+            }
+        }
+
+        if line.starts_with("@@") {
+            self.hunk = parse_hunk_counts(line).unwrap_or(HunkState::Open);
+            return DiffLine::Hunk;
+        }
+        match line.chars().next() {
+            Some('+') => DiffLine::Added(&line[1..]),
+            Some('-') => DiffLine::Removed(&line[1..]),
+            Some(' ') => DiffLine::Context(&line[1..]),
+            _ => DiffLine::Other,
+        }
+    }
+
+    /// Classify a line outside of a hunk as a file header, if it is one.
+    fn classify_header<'a>(&mut self, line: &'a str) -> Option<DiffLine<'a>> {
+        let path = if let Some(rest) = line.strip_prefix("diff --git ") {
+            rest.split_whitespace().last().map(strip_prefix_dir)
+        } else if let Some(rest) = line.strip_prefix("+++ ") {
+            let path = rest.split('\t').next().unwrap_or_default().trim();
+            (path != "/dev/null").then(|| strip_prefix_dir(path))
+        } else if line.starts_with("--- ") {
+            None
+        } else if let Some(rest) = line.strip_prefix("*** ") {
+            rest.split_once("File: ")
+                .or_else(|| rest.split_once("Move to: "))
+                .map(|(_, path)| path.trim())
+        } else if METADATA_PREFIXES
+            .iter()
+            .any(|prefix| line.starts_with(prefix))
+        {
+            None
+        } else {
+            return None;
+        };
+        self.hunk = HunkState::Outside;
+        Some(DiffLine::FileHeader(path.filter(|path| !path.is_empty())))
+    }
+}
+
+/// Strip the `a/` or `b/` prefix git puts on paths.
+fn strip_prefix_dir(path: &str) -> &str {
+    path.strip_prefix("a/")
+        .or_else(|| path.strip_prefix("b/"))
+        .unwrap_or(path)
+}
+
+/// Parse the line counts of a hunk header like `@@ -1,3 +1,4 @@`.
+///
+/// Omitted counts default to 1, as in unified diffs.
+fn parse_hunk_counts(line: &str) -> Option<HunkState> {
+    let mut ranges = line.trim_start_matches('@').split_whitespace();
+    let count = |range: &str| match range.split_once(',') {
+        Some((_, count)) => count.parse().ok(),
+        None => range.parse::<usize>().ok().map(|_| 1),
+    };
+    let old = count(ranges.next()?.strip_prefix('-')?)?;
+    let new = count(ranges.next()?.strip_prefix('+')?)?;
+    Some(HunkState::Counted { old, new })
+}
+
+/// Styles for the parts of a diff.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct DiffStyles {
+    pub(crate) added: Style,
+    pub(crate) removed: Style,
+    pub(crate) hunk: Style,
+    pub(crate) file_header: Style,
+}
+
+impl DiffStyles {
+    pub(crate) fn from_theme(theme: &Theme) -> Self {
+        Self {
+            added: theme.diff_added.clone(),
+            removed: theme.diff_removed.clone(),
+            hunk: theme.diff_hunk.clone(),
+            file_header: theme.diff_file_header.clone(),
+        }
+    }
+}
+
+/// Raw SGR escape sequence that switches to a style, without resetting
+/// afterwards, or `None` for an empty style.
+///
+/// Used where a style has to span text carrying its own escapes, such as
+/// syntax highlighted code on a full-width background.
+pub(crate) fn style_escape(style: &Style) -> Option<String> {
+    let mut codes = Vec::new();
+    if style.bold {
+        codes.push("1".to_string());
+    }
+    if style.dimmed {
+        codes.push("2".to_string());
+    }
+    if style.italic {
+        codes.push("3".to_string());
+    }
+    if style.underline {
+        codes.push("4".to_string());
+    }
+    if let Some(fg) = style.fg {
+        codes.push(fg.to_fg_str().into_owned());
+    }
+    if let Some(bg) = style.bg {
+        codes.push(bg.to_bg_str().into_owned());
+    }
+    (!codes.is_empty()).then(|| format!("\x1b[{}m", codes.join(";")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classify_all(diff: &str) -> Vec<String> {
+        let mut classifier = DiffClassifier::default();
+        diff.lines()
+            .map(|line| format!("{:?}", classifier.classify(line)))
+            .collect()
+    }
+
+    #[test]
+    fn test_git_diff() {
+        let diff = "diff --git a/src/lib.rs b/src/lib.rs\n\
+                    index 83db48f..bf269f4 100644\n\
+                    --- a/src/lib.rs\n\
+                    +++ b/src/lib.rs\n\
+                    @@ -1,3 +1,3 @@\n\
+                    \x20fn main() {\n\
+                    -    old();\n\
+                    +    new();\n\
+                    \x20}";
+        insta::assert_snapshot!(classify_all(diff).join("\n"), @r#"
+        FileHeader(Some("src/lib.rs"))
+        FileHeader(None)
+        FileHeader(None)
+        FileHeader(Some("src/lib.rs"))
+        Hunk
+        Context("fn main() {")
+        Removed("    old();")
+        Added("    new();")
+        Context("}")
+        "#);
+    }
+
+    #[test]
+    fn test_removed_line_looking_like_header() {
+        let diff = "@@ -1,2 +0,0 @@\n--- removed comment\n-x\n--- a/next.rs";
+        insta::assert_snapshot!(classify_all(diff).join("\n"), @r#"
+        Hunk
+        Removed("-- removed comment")
+        Removed("x")
+        FileHeader(None)
+        "#);
+    }
+
+    #[test]
+    fn test_apply_patch() {
+        let diff = "*** Begin Patch\n\
+                    *** Update File: app/main.py\n\
+                    @@ def main():\n\
+                    -    print(1)\n\
+                    +    print(2)\n\
+                    *** End Patch";
+        insta::assert_snapshot!(classify_all(diff).join("\n"), @r#"
+        FileHeader(None)
+        FileHeader(Some("app/main.py"))
+        Hunk
+        Removed("    print(1)")
+        Added("    print(2)")
+        FileHeader(None)
+        "#);
+    }
+
+    #[test]
+    fn test_hunk_counts() {
+        assert_eq!(
+            parse_hunk_counts("@@ -1,3 +1,4 @@ fn main"),
+            Some(HunkState::Counted { old: 3, new: 4 })
+        );
+        assert_eq!(
+            parse_hunk_counts("@@ -5 +5 @@"),
+            Some(HunkState::Counted { old: 1, new: 1 })
+        );
+        assert_eq!(parse_hunk_counts("@@ def main():"), None);
+    }
+
+    #[test]
+    fn test_diff_labels() {
+        assert!(is_diff_label("diff"));
+        assert!(is_diff_label("Patch"));
+        assert!(!is_diff_label("rust"));
+    }
+}
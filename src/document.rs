@@ -0,0 +1,123 @@
+//! Rendering of complete markdown documents.
+
+use std::io;
+
+use streamdown_parser::Parser;
+
+use crate::config::RendererConfig;
+use crate::flush::FlushPolicy;
+use crate::render_line;
+use crate::renderer::Renderer;
+use crate::theme::Theme;
+
+/// Render a complete markdown document to a string.
+///
+/// Intended for stored chat history and tests, where the whole document is
+/// available up front. Lines are parsed directly from the input without the
+/// token buffering done by [`StreamdownRenderer`](crate::StreamdownRenderer).
+pub fn render(markdown: &str, width: usize, theme: &Theme) -> String {
+    render_with_config(
+        markdown,
+        &RendererConfig::new().width(width).theme(theme.clone()),
+    )
+}
+
+/// Render a complete markdown document to a string, with the layout and code
+/// block options of a configuration.
+///
+/// Set a [theme](RendererConfig::theme) on the configuration when rendering
+/// many documents, so the terminal is not queried for its background on
+/// every call.
+pub fn render_with_config(markdown: &str, config: &RendererConfig) -> String {
+    let mut output = Vec::with_capacity(markdown.len() * 2);
+    render_into(markdown, config, &mut output).expect("writing to a Vec cannot fail");
+    String::from_utf8(output)
+        .unwrap_or_else(|err| String::from_utf8_lossy(err.as_bytes()).into_owned())
+}
+
+/// Render a complete markdown document to a list of output lines.
+pub fn render_lines(markdown: &str, width: usize, theme: &Theme) -> Vec<String> {
+    render(markdown, width, theme)
+        .lines()
+        .map(String::from)
+        .collect()
+}
+
+fn render_into(markdown: &str, config: &RendererConfig, output: &mut Vec<u8>) -> io::Result<()> {
+    let mut parser = Parser::new();
+    let mut renderer = Renderer::from_config(output, config);
+    renderer.set_flush_policy(FlushPolicy::Manual);
+
+    // Split like the streaming renderer, which keeps a `\r` before `\n`
+    for line in markdown.split_terminator('\n') {
+        render_line(&mut parser, &mut renderer, &mut [], line)?;
+    }
+    for event in parser.finalize() {
+        renderer.render_event(&event)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strip_ansi(s: &str) -> String {
+        let bytes = strip_ansi_escapes::strip(s);
+        String::from_utf8(bytes).unwrap()
+    }
+
+    #[test]
+    fn test_render_paragraph() {
+        let output = strip_ansi(&render("Hello **world**\n", 80, &Theme::dark()));
+        assert!(output.contains("Hello world"));
+    }
+
+    #[test]
+    fn test_render_closes_open_code_block() {
+        let output = strip_ansi(&render("```rust\nfn main() {}", 80, &Theme::dark()));
+        assert!(output.contains("fn main() {}"));
+    }
+
+    #[test]
+    fn test_render_matches_streaming_renderer() {
+        let markdown = "# Title\n\nSome text\n\n- one\n- two\n\n```\ncode\n```\n";
+        let mut streaming = crate::StreamdownRenderer::with_theme(Vec::new(), 80, Theme::dark());
+        streaming.push(markdown).unwrap();
+        let expected = String::from_utf8(streaming.finish().unwrap()).unwrap();
+        assert_eq!(render(markdown, 80, &Theme::dark()), expected);
+    }
+
+    #[test]
+    fn test_render_crlf_matches_streaming_renderer() {
+        let markdown = "Some text\r\n\r\n- one\r\n- two\r\nlast";
+        let mut streaming = crate::StreamdownRenderer::with_theme(Vec::new(), 80, Theme::dark());
+        streaming.push(markdown).unwrap();
+        let expected = String::from_utf8(streaming.finish().unwrap()).unwrap();
+        assert_eq!(render(markdown, 80, &Theme::dark()), expected);
+    }
+
+    #[test]
+    fn test_render_with_config() {
+        let config = RendererConfig::new()
+            .width(40)
+            .padding(2)
+            .theme(Theme::dark());
+        let output = strip_ansi(&render_with_config("# Title\n", &config));
+        assert!(output.starts_with("  "));
+    }
+
+    #[test]
+    fn test_render_lines() {
+        let lines = render_lines("one\ntwo\n", 80, &Theme::dark());
+        let stripped: Vec<String> = lines.iter().map(|line| strip_ansi(line)).collect();
+        assert!(stripped.iter().any(|line| line.contains("one")));
+        assert!(stripped.iter().any(|line| line.contains("two")));
+        assert!(lines.iter().all(|line| !line.contains('\n')));
+    }
+
+    #[test]
+    fn test_render_empty() {
+        assert!(render("", 80, &Theme::dark()).is_empty());
+    }
+}
@@ -0,0 +1,96 @@
+//! Error types for the markdown renderer.
+
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Errors produced while rendering.
+#[derive(Debug)]
+pub enum Error {
+    /// Writing to the underlying writer failed.
+    Io(io::Error),
+    /// Pushed bytes were not valid UTF-8.
+    InvalidUtf8 {
+        /// Offset of the invalid sequence in the byte stream pushed so far.
+        offset: usize,
+        /// The invalid bytes, which were skipped.
+        bytes: Vec<u8>,
+    },
+    /// A code theme name does not match any loaded syntect theme.
+    UnknownCodeTheme {
+        /// The requested theme name.
+        name: String,
+        /// Names of the themes that are available.
+        available: Vec<String>,
+    },
+    /// A code theme file could not be loaded.
+    CodeThemeLoad {
+        /// Path of the theme file.
+        path: PathBuf,
+        /// The underlying loading error.
+        source: syntect::LoadingError,
+    },
+    /// Extra syntax definitions could not be loaded.
+    SyntaxLoad {
+        /// Path of the syntax folder or dump file.
+        path: PathBuf,
+        /// The underlying loading error.
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "write failed: {}", err),
+            Error::InvalidUtf8 { offset, bytes } => {
+                write!(
+                    f,
+                    "invalid UTF-8 sequence {:02x?} at byte {}",
+                    bytes, offset
+                )
+            }
+            Error::UnknownCodeTheme { name, available } => {
+                write!(
+                    f,
+                    "unknown code theme `{}` (available: {})",
+                    name,
+                    available.join(", ")
+                )
+            }
+            Error::CodeThemeLoad { path, source } => {
+                write!(
+                    f,
+                    "failed to load code theme {}: {}",
+                    path.display(),
+                    source
+                )
+            }
+            Error::SyntaxLoad { path, source } => {
+                write!(
+                    f,
+                    "failed to load syntaxes from {}: {}",
+                    path.display(),
+                    source
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::CodeThemeLoad { source, .. } => Some(source),
+            Error::SyntaxLoad { source, .. } => Some(source.as_ref()),
+            Error::InvalidUtf8 { .. } | Error::UnknownCodeTheme { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
@@ -0,0 +1,59 @@
+//! Policies controlling how often rendered output is flushed.
+
+use std::time::Duration;
+
+/// When the renderer flushes its writer.
+///
+/// The policy decides which events flush while a pushed chunk is rendered.
+/// Output still pending afterwards is flushed before the push returns if the
+/// policy allows it then, so a stalled stream is not held back: always for
+/// [`FlushPolicy::Event`] and [`FlushPolicy::Line`], outside code blocks for
+/// [`FlushPolicy::Block`], and once the interval has passed for
+/// [`FlushPolicy::Interval`]. Output is always flushed when a message ends,
+/// finishes, or is interrupted, and whenever a live preview is drawn.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FlushPolicy {
+    /// Flush after every parse event. Most responsive, most syscalls.
+    #[default]
+    Event,
+    /// Flush after each event that completes an output line.
+    Line,
+    /// Flush after events that end a block (paragraph, code block, table, ...).
+    Block,
+    /// Flush after an event once at least this much time has passed since
+    /// the previous flush.
+    Interval(Duration),
+    /// Never flush automatically.
+    Manual,
+}
+
+impl FlushPolicy {
+    /// Flush at most `fps` times per second while rendering a pushed chunk,
+    /// e.g. `FlushPolicy::frame_rate(60)`.
+    ///
+    /// A rate of 0 is treated as 1 frame per second.
+    pub fn frame_rate(fps: u32) -> Self {
+        Self::Interval(Duration::from_secs(1) / fps.max(1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_rate() {
+        assert_eq!(
+            FlushPolicy::frame_rate(50),
+            FlushPolicy::Interval(Duration::from_millis(20))
+        );
+    }
+
+    #[test]
+    fn test_frame_rate_zero_does_not_panic() {
+        assert_eq!(
+            FlushPolicy::frame_rate(0),
+            FlushPolicy::Interval(Duration::from_secs(1))
+        );
+    }
+}
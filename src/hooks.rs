@@ -0,0 +1,173 @@
+//! Hooks for observing and rewriting parse events as they stream.
+
+use std::io::{self, Write};
+
+use streamdown_parser::ParseEvent;
+
+use crate::renderer::Renderer;
+
+/// What to do with an event after [`EventHook::before_event`] has seen it.
+#[derive(Debug)]
+pub enum HookAction {
+    /// Render the event as usual.
+    Continue,
+    /// Drop the event.
+    Suppress,
+    /// Render these events instead.
+    Replace(Vec<ParseEvent>),
+}
+
+/// Observer invoked for every parse event, before and after it is rendered.
+///
+/// Hooks run in the order they were added, each wrapping the ones added after
+/// it. Events a hook replaces or injects are passed on to the later hooks but
+/// not back to the hook itself.
+pub trait EventHook: Send {
+    /// Called before an event is rendered.
+    fn before_event(&mut self, _event: &ParseEvent) -> HookAction {
+        HookAction::Continue
+    }
+
+    /// Called after an event has been rendered (or suppressed or replaced by
+    /// a later hook). Returned events are rendered right after it.
+    fn after_event(&mut self, _event: &ParseEvent) -> Vec<ParseEvent> {
+        Vec::new()
+    }
+}
+
+/// Render an event through a chain of hooks.
+pub(crate) fn dispatch<W: Write>(
+    hooks: &mut [Box<dyn EventHook>],
+    renderer: &mut Renderer<W>,
+    event: &ParseEvent,
+) -> io::Result<()> {
+    let Some((hook, rest)) = hooks.split_first_mut() else {
+        return renderer.render_event(event);
+    };
+
+    match hook.before_event(event) {
+        HookAction::Continue => dispatch(rest, renderer, event)?,
+        HookAction::Suppress => return Ok(()),
+        HookAction::Replace(events) => {
+            for replacement in &events {
+                dispatch(rest, renderer, replacement)?;
+            }
+        }
+    }
+
+    for injected in hook.after_event(event) {
+        dispatch(rest, renderer, &injected)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::theme::Theme;
+
+    fn render(hooks: &mut [Box<dyn EventHook>], events: &[ParseEvent]) -> String {
+        let mut output = Vec::new();
+        let mut renderer = Renderer::with_theme(&mut output, 80, Theme::dark());
+        for event in events {
+            dispatch(hooks, &mut renderer, event).unwrap();
+        }
+        strip_ansi_escapes::strip_str(String::from_utf8(output).unwrap())
+    }
+
+    struct SuppressHeadings;
+
+    impl EventHook for SuppressHeadings {
+        fn before_event(&mut self, event: &ParseEvent) -> HookAction {
+            match event {
+                ParseEvent::Heading { .. } => HookAction::Suppress,
+                _ => HookAction::Continue,
+            }
+        }
+    }
+
+    struct AfterRule;
+
+    impl EventHook for AfterRule {
+        fn after_event(&mut self, event: &ParseEvent) -> Vec<ParseEvent> {
+            match event {
+                ParseEvent::HorizontalRule => {
+                    vec![ParseEvent::Text("[apply]".into()), ParseEvent::Newline]
+                }
+                _ => Vec::new(),
+            }
+        }
+    }
+
+    struct Uppercase;
+
+    impl EventHook for Uppercase {
+        fn before_event(&mut self, event: &ParseEvent) -> HookAction {
+            match event {
+                ParseEvent::Text(text) => {
+                    HookAction::Replace(vec![ParseEvent::Text(text.to_uppercase())])
+                }
+                _ => HookAction::Continue,
+            }
+        }
+    }
+
+    #[derive(Default)]
+    struct Counter {
+        seen: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    impl EventHook for Counter {
+        fn before_event(&mut self, _event: &ParseEvent) -> HookAction {
+            self.seen.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            HookAction::Continue
+        }
+    }
+
+    #[test]
+    fn test_no_hooks_renders_event() {
+        let output = render(&mut [], &[ParseEvent::Text("plain".into())]);
+        assert_eq!(output, "plain");
+    }
+
+    #[test]
+    fn test_suppress() {
+        let mut hooks: Vec<Box<dyn EventHook>> = vec![Box::new(SuppressHeadings)];
+        let output = render(
+            &mut hooks,
+            &[
+                ParseEvent::Heading {
+                    level: 3,
+                    content: "Hidden".into(),
+                },
+                ParseEvent::Text("shown".into()),
+            ],
+        );
+        assert_eq!(output, "shown");
+    }
+
+    #[test]
+    fn test_inject_after_event() {
+        let mut hooks: Vec<Box<dyn EventHook>> = vec![Box::new(AfterRule)];
+        let output = render(
+            &mut hooks,
+            &[
+                ParseEvent::Text("before".into()),
+                ParseEvent::Newline,
+                ParseEvent::HorizontalRule,
+            ],
+        );
+        assert!(output.starts_with("before\n─"));
+        assert!(output.ends_with("─\n[apply]\n"));
+    }
+
+    #[test]
+    fn test_replaced_events_reach_later_hooks() {
+        let counter = Counter::default();
+        let seen = counter.seen.clone();
+        let mut hooks: Vec<Box<dyn EventHook>> = vec![Box::new(Uppercase), Box::new(counter)];
+        let output = render(&mut hooks, &[ParseEvent::Text("loud".into())]);
+        assert_eq!(output, "LOUD");
+        assert_eq!(seen.load(std::sync::atomic::Ordering::Relaxed), 1);
+    }
+}
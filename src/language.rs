@@ -0,0 +1,187 @@
+//! Resolution of code fence labels to syntect syntaxes.
+//!
+//! Fence labels are looked up as syntect tokens first, then through an alias
+//! table for labels syntect does not know. Unlabeled blocks are detected from
+//! their first line.
+
+use std::collections::HashMap;
+
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+
+/// Fence labels commonly emitted by LLMs, mapped to tokens of the default
+/// syntax set.
+const DEFAULT_ALIASES: &[(&str, &str)] = &[
+    ("c#", "cs"),
+    ("c++", "cpp"),
+    ("cjs", "js"),
+    ("csharp", "cs"),
+    ("golang", "go"),
+    ("javascript", "js"),
+    ("json5", "json"),
+    ("jsonc", "json"),
+    ("jsx", "js"),
+    ("mjs", "js"),
+    ("objc", "m"),
+    ("plaintext", "txt"),
+    ("py3", "python"),
+    ("python3", "python"),
+    ("rs", "rust"),
+    ("shell", "bash"),
+    ("text", "txt"),
+    ("ts", "js"),
+    ("tsx", "js"),
+    ("typescript", "js"),
+    ("yml", "yaml"),
+    ("zsh", "bash"),
+];
+
+/// First lines that identify a language, checked in order: a prefix matched
+/// ignoring ASCII case, a suffix the line must end with, and the language.
+const SIGNATURES: &[(&str, &str, &str)] = &[
+    ("<?php", "", "php"),
+    ("<?xml", "", "xml"),
+    ("<!DOCTYPE html", "", "html"),
+    ("<html", "", "html"),
+    ("diff --git ", "", "diff"),
+    ("--- a/", "", "diff"),
+    ("#include ", "", "cpp"),
+    ("package main", "", "go"),
+    ("fn main(", "", "rust"),
+    ("use std::", "", "rust"),
+    // Ruby methods start with `def` too, but without the colon
+    ("def ", ":", "python"),
+    ("from __future__ ", "", "python"),
+    ("SELECT ", "", "sql"),
+    ("CREATE TABLE ", "", "sql"),
+];
+
+/// The default alias table.
+pub(crate) fn default_aliases() -> HashMap<String, String> {
+    DEFAULT_ALIASES
+        .iter()
+        .map(|(alias, language)| (alias.to_string(), language.to_string()))
+        .collect()
+}
+
+/// Resolve a fence label to a syntax.
+///
+/// The label is tried as a syntect token first, so syntaxes added with
+/// [`HighlightAssets::with_syntax_folder`](crate::HighlightAssets::with_syntax_folder)
+/// take precedence over aliases.
+pub(crate) fn resolve_syntax<'a>(
+    syntax_set: &'a SyntaxSet,
+    aliases: &HashMap<String, String>,
+    label: &str,
+) -> Option<&'a SyntaxReference> {
+    syntax_set.find_syntax_by_token(label).or_else(|| {
+        aliases
+            .get(&label.to_lowercase())
+            .and_then(|language| syntax_set.find_syntax_by_token(language))
+    })
+}
+
+/// Detect the syntax of an unlabeled block from its first line.
+///
+/// Uses syntect's first-line patterns, then the interpreter named by a
+/// shebang, then a few well-known line prefixes.
+pub(crate) fn detect_syntax<'a>(
+    syntax_set: &'a SyntaxSet,
+    aliases: &HashMap<String, String>,
+    first_line: &str,
+) -> Option<&'a SyntaxReference> {
+    syntax_set
+        .find_syntax_by_first_line(first_line)
+        .or_else(|| {
+            shebang_interpreter(first_line)
+                .and_then(|interpreter| resolve_syntax(syntax_set, aliases, interpreter))
+        })
+        .or_else(|| {
+            let line = first_line.trim();
+            SIGNATURES
+                .iter()
+                .find(|(prefix, suffix, _)| {
+                    line.get(..prefix.len())
+                        .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+                        && line.ends_with(suffix)
+                })
+                .and_then(|(_, _, language)| resolve_syntax(syntax_set, aliases, language))
+        })
+}
+
+/// The interpreter named by a shebang line, without path or version suffix.
+fn shebang_interpreter(line: &str) -> Option<&str> {
+    let mut words = line.strip_prefix("#!")?.split_whitespace();
+    let mut program = words.next()?.rsplit('/').next()?;
+    if program == "env" {
+        program = words.find(|word| !word.starts_with('-'))?;
+    }
+    let name = program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    (!name.is_empty()).then_some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn syntax_name(label: &str) -> Option<String> {
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        resolve_syntax(&syntax_set, &default_aliases(), label).map(|syntax| syntax.name.clone())
+    }
+
+    fn detected_name(first_line: &str) -> Option<String> {
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        detect_syntax(&syntax_set, &default_aliases(), first_line).map(|syntax| syntax.name.clone())
+    }
+
+    #[test]
+    fn test_aliases_resolve() {
+        assert_eq!(syntax_name("golang").as_deref(), Some("Go"));
+        assert_eq!(syntax_name("py3").as_deref(), Some("Python"));
+        assert_eq!(syntax_name("jsonc").as_deref(), Some("JSON"));
+        assert_eq!(syntax_name("tsx").as_deref(), Some("JavaScript"));
+        assert_eq!(syntax_name("zsh"), syntax_name("bash"));
+        assert_eq!(syntax_name("Shell"), syntax_name("bash"));
+    }
+
+    #[test]
+    fn test_unknown_label() {
+        assert_eq!(syntax_name("no-such-language"), None);
+    }
+
+    #[test]
+    fn test_shebang_interpreter() {
+        assert_eq!(shebang_interpreter("#!/bin/bash"), Some("bash"));
+        assert_eq!(
+            shebang_interpreter("#!/usr/bin/env python3"),
+            Some("python")
+        );
+        assert_eq!(
+            shebang_interpreter("#!/usr/bin/env -S node --flag"),
+            Some("node")
+        );
+        assert_eq!(shebang_interpreter("echo hi"), None);
+    }
+
+    #[test]
+    fn test_detect_from_first_line() {
+        assert_eq!(
+            detected_name("#!/usr/bin/env python3").as_deref(),
+            Some("Python")
+        );
+        assert_eq!(detected_name("<?php echo 1;").as_deref(), Some("PHP"));
+        assert_eq!(detected_name("package main").as_deref(), Some("Go"));
+        assert_eq!(detected_name("use std::io;").as_deref(), Some("Rust"));
+        assert_eq!(detected_name("hello world"), None);
+    }
+
+    #[test]
+    fn test_detect_signature_details() {
+        assert_eq!(detected_name("def greet(name):").as_deref(), Some("Python"));
+        assert_eq!(detected_name("def greet(name)"), None);
+        assert_eq!(
+            detected_name("select * from users;").as_deref(),
+            Some("SQL")
+        );
+        assert_eq!(detected_name("<!doctype html>").as_deref(), Some("HTML"));
+    }
+}
@@ -6,11 +6,12 @@
 //! # Example
 //!
 //! ```no_run
-//! use forge_markdown_stream::StreamdownRenderer;
+//! use forge_markdown_stream::{RendererConfig, StreamdownRenderer};
 //! use std::io;
 //!
 //! fn main() -> io::Result<()> {
-//!     let mut renderer = StreamdownRenderer::new(io::stdout(), 80);
+//!     // Detects the terminal width and theme
+//!     let mut renderer = StreamdownRenderer::from_config(io::stdout(), RendererConfig::new());
 //!     
 //!     // Push tokens as they arrive from LLM
 //!     renderer.push("Hello ")?;
@@ -21,24 +22,70 @@
 //!     Ok(())
 //! }
 //! ```
+//!
+//! Complete documents, such as stored chat history, can be rendered in one go:
+//!
+//! ```no_run
+//! use forge_markdown_stream::{render, Theme};
+//!
+//! let output = render("# Hello\n\nSome **markdown**", 80, &Theme::default());
+//! print!("{}", output);
+//! ```
+//!
+//! # Features
+//!
+//! - `async`: enables [`AsyncStreamdownRenderer`], which renders a
+//!   `futures::Stream` of tokens into a `tokio::io::AsyncWrite`.
+//! - `resize` (Linux only): enables [`ResizeWatcher`] and
+//!   [`StreamdownRenderer::watch_resize`] to follow terminal resizes.
 
+#[cfg(feature = "async")]
+mod async_renderer;
 mod code;
+mod code_block;
+mod config;
+mod diff;
+mod document;
+mod error;
+mod flush;
 mod heading;
+mod hooks;
 mod inline;
+mod language;
 mod list;
 mod renderer;
 mod repair;
+#[cfg(all(feature = "resize", target_os = "linux"))]
+mod resize;
+mod session;
+mod stats;
 mod table;
 mod theme;
 mod style;
 mod utils;
 
 use std::io::{self, Write};
+use std::ops::Range;
+
+use hooks::dispatch;
+
+pub use streamdown_parser::{InlineElement, ListBullet, ParseEvent, Parser};
 
-pub use streamdown_parser::Parser;
+#[cfg(feature = "async")]
+pub use async_renderer::AsyncStreamdownRenderer;
 
+pub use code::{CodeBlockStyle, CodeCollapse, CodeOverflow, CodeTheme, HighlightAssets};
+pub use code_block::CodeBlock;
+pub use config::RendererConfig;
+pub use document::{render, render_lines, render_with_config};
+pub use error::Error;
+pub use flush::FlushPolicy;
+pub use hooks::{EventHook, HookAction};
 pub use renderer::Renderer;
 pub use repair::repair_line;
+#[cfg(all(feature = "resize", target_os = "linux"))]
+pub use resize::ResizeWatcher;
+pub use stats::RenderStats;
 pub use theme::{Style, Theme};
 
 /// Streaming markdown renderer for terminal output.
@@ -50,62 +97,750 @@ pub use theme::{Style, Theme};
 pub struct StreamdownRenderer<W: Write> {
     parser: Parser,
     renderer: Renderer<W>,
+    hooks: Vec<Box<dyn EventHook>>,
     line_buffer: String,
+    // Length of the start of `line_buffer` known to contain no newline
+    scanned: usize,
+    live_preview: bool,
+    // Trailing bytes of an incomplete UTF-8 sequence from `push_bytes`
+    utf8_pending: Vec<u8>,
+    bytes_pushed: usize,
+    input_lines: usize,
+    #[cfg(all(feature = "resize", target_os = "linux"))]
+    resize_watcher: Option<ResizeWatcher>,
 }
 
 impl<W: Write> StreamdownRenderer<W> {
     /// Create a new renderer with the given writer and terminal width.
     pub fn new(writer: W, width: usize) -> Self {
-        Self {
-            parser: Parser::new(),
-            renderer: Renderer::new(writer, width),
-            line_buffer: String::new(),
-        }
+        Self::from_config(writer, RendererConfig::new().width(width))
     }
 
     /// Create a new renderer with a custom theme.
     pub fn with_theme(writer: W, width: usize, theme: Theme) -> Self {
+        Self::from_config(writer, RendererConfig::new().width(width).theme(theme))
+    }
+
+    /// Create a new renderer from a configuration.
+    pub fn from_config(writer: W, config: RendererConfig) -> Self {
         Self {
             parser: Parser::new(),
-            renderer: Renderer::with_theme(writer, width, theme),
+            renderer: Renderer::from_config(writer, &config),
+            hooks: Vec::new(),
             line_buffer: String::new(),
+            scanned: 0,
+            live_preview: config.live_preview,
+            utf8_pending: Vec::new(),
+            bytes_pushed: 0,
+            input_lines: 0,
+            #[cfg(all(feature = "resize", target_os = "linux"))]
+            resize_watcher: None,
         }
     }
 
+    /// Enable or disable live preview of the in-progress line.
+    ///
+    /// When enabled, incomplete lines are rendered immediately with best-effort
+    /// inline styling, then erased and re-rendered once the line completes.
+    /// This relies on cursor-control sequences, so only enable it when writing
+    /// to an interactive terminal.
+    pub fn set_live_preview(&mut self, enabled: bool) {
+        self.live_preview = enabled;
+    }
+
+    /// Set the terminal width.
+    ///
+    /// Applies to everything rendered afterwards, including buffered tables.
+    pub fn set_width(&mut self, width: usize) {
+        self.renderer.set_width(width);
+    }
+
+    /// Add a hook that is invoked for every parse event.
+    ///
+    /// Hooks can observe, suppress, replace, or inject events; see [`EventHook`].
+    pub fn add_hook(&mut self, hook: impl EventHook + 'static) {
+        self.hooks.push(Box::new(hook));
+    }
+
+    /// Set when the writer is flushed.
+    pub fn set_flush_policy(&mut self, policy: FlushPolicy) {
+        self.renderer.set_flush_policy(policy);
+    }
+
+    /// Flush the underlying writer.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.renderer.flush()
+    }
+
+    /// Follow terminal resizes automatically.
+    ///
+    /// The width is updated from `SIGWINCH` before each line is rendered,
+    /// including the partial line rendered by [`StreamdownRenderer::finish`],
+    /// [`StreamdownRenderer::end_message`] and [`StreamdownRenderer::interrupt`].
+    #[cfg(all(feature = "resize", target_os = "linux"))]
+    pub fn watch_resize(&mut self) -> io::Result<()> {
+        self.resize_watcher = Some(ResizeWatcher::new()?);
+        Ok(())
+    }
+
     /// Push a token to the renderer.
     ///
     /// Tokens are buffered until a complete line is received, then rendered.
+    /// With live preview enabled, the incomplete remainder is shown as well.
     pub fn push(&mut self, token: &str) -> io::Result<()> {
         self.line_buffer.push_str(token);
 
-        while let Some(pos) = self.line_buffer.find('\n') {
-            let line = self.line_buffer[..pos].to_string();
+        // Only scan what earlier pushes have not. Complete lines are rendered
+        // straight from the buffer, which is compacted once at the end
+        // instead of once per line.
+        let mut start = 0;
+        let mut search_from = self.scanned;
+        let mut result = Ok(());
+        while let Some(pos) = self.line_buffer[search_from..].find('\n') {
+            let end = search_from + pos;
+            result = self.render_buffered_line(start..end);
+            start = end + 1;
+            search_from = start;
+            if result.is_err() {
+                break;
+            }
+        }
+        // A line that failed to render is dropped like the rendered ones;
+        // the lines after it are rendered by the next push.
+        self.line_buffer.drain(..start);
+        self.scanned = if result.is_ok() {
+            self.line_buffer.len()
+        } else {
+            0
+        };
+        result?;
 
-            for repaired in repair_line(&line, self.parser.state()) {
-                for event in self.parser.parse_line(&repaired) {
-                    self.renderer.render_event(&event)?;
+        if self.live_preview {
+            self.apply_resize();
+            let in_code = self.parser.state().is_in_code();
+            self.renderer.render_preview(&self.line_buffer, in_code)?;
+        }
+        self.renderer.flush_pending()
+    }
+
+    /// Push raw bytes to the renderer.
+    ///
+    /// Multi-byte UTF-8 sequences split across chunks are buffered until the
+    /// rest of the sequence arrives. Invalid sequences are skipped and the
+    /// rest of the chunk is still rendered, after which the first invalid
+    /// sequence is reported as [`Error::InvalidUtf8`].
+    pub fn push_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        let mut offset = self.bytes_pushed - self.utf8_pending.len();
+        self.bytes_pushed += bytes.len();
+
+        let mut pending = std::mem::take(&mut self.utf8_pending);
+        pending.extend_from_slice(bytes);
+
+        let mut rest = pending.as_slice();
+        let mut first_error = None;
+        loop {
+            match std::str::from_utf8(rest) {
+                Ok(text) => {
+                    self.push(text)?;
+                    break;
+                }
+                Err(err) => {
+                    let (valid, invalid) = rest.split_at(err.valid_up_to());
+                    self.push(std::str::from_utf8(valid).unwrap_or_default())?;
+                    let Some(len) = err.error_len() else {
+                        // Incomplete sequence at the end: wait for the next chunk
+                        self.utf8_pending = invalid.to_vec();
+                        break;
+                    };
+                    first_error.get_or_insert(Error::InvalidUtf8 {
+                        offset: offset + valid.len(),
+                        bytes: invalid[..len].to_vec(),
+                    });
+                    offset += valid.len() + len;
+                    rest = &invalid[len..];
                 }
             }
+        }
 
-            self.line_buffer = self.line_buffer[pos + 1..].to_string();
+        match first_error {
+            Some(err) => Err(err),
+            None => Ok(()),
         }
-        Ok(())
     }
 
     /// Finish rendering, flushing any remaining buffered content.
     /// Returns the underlying writer.
-    pub fn finish(mut self) -> io::Result<()> {
-        if !self.line_buffer.is_empty() {
-            for repaired in repair_line(&self.line_buffer, self.parser.state()) {
-                for event in self.parser.parse_line(&repaired) {
-                    self.renderer.render_event(&event)?;
-                }
-            }
+    pub fn finish(mut self) -> io::Result<W> {
+        self.render_remaining()?;
+        self.renderer.flush()?;
+        Ok(self.into_inner())
+    }
+
+    /// End the current message, keeping the renderer for the next one.
+    ///
+    /// Renders any buffered content, closes open blocks, and resets parser,
+    /// list, and blockquote state. Returns the statistics for the message.
+    pub fn end_message(&mut self) -> io::Result<RenderStats> {
+        let result = self.render_remaining();
+        // A failed render leaves input behind; it belongs to this message
+        self.line_buffer.clear();
+        self.scanned = 0;
+        self.utf8_pending.clear();
+        self.parser = Parser::new();
+        self.renderer.reset();
+        self.bytes_pushed = 0;
+        let mut stats = self.renderer.take_stats();
+        stats.input_lines = std::mem::take(&mut self.input_lines);
+        result?;
+        self.renderer.flush()?;
+        Ok(stats)
+    }
+
+    /// Interrupt the current message, e.g. when the user cancels generation.
+    ///
+    /// The buffered partial line is rendered, then buffered tables are flushed,
+    /// open blocks are closed, ANSI attributes are reset, and a themed
+    /// "interrupted" marker is printed. The renderer is ready for the next
+    /// message afterwards.
+    ///
+    /// An open code block is ended with a `CodeBlockEnd` event, so hooks see
+    /// it close and the partial block is kept in
+    /// [`StreamdownRenderer::code_blocks`].
+    pub fn interrupt(&mut self) -> io::Result<RenderStats> {
+        self.render_partial_line()?;
+        if self.parser.state().is_in_code() {
+            dispatch(&mut self.hooks, &mut self.renderer, &ParseEvent::CodeBlockEnd)?;
+        }
+        self.renderer.interrupt()?;
+
+        self.parser = Parser::new();
+        self.utf8_pending.clear();
+        self.bytes_pushed = 0;
+        let mut stats = self.renderer.take_stats();
+        stats.input_lines = std::mem::take(&mut self.input_lines);
+        Ok(stats)
+    }
+
+    /// Get the code blocks completed so far.
+    pub fn code_blocks(&self) -> &[CodeBlock] {
+        self.renderer.code_blocks()
+    }
+
+    /// Take the code blocks completed so far, e.g. to offer "copy block N".
+    ///
+    /// Block indices and line ranges restart with every message, so take the
+    /// blocks after each [`StreamdownRenderer::end_message`].
+    pub fn take_code_blocks(&mut self) -> Vec<CodeBlock> {
+        self.renderer.take_code_blocks()
+    }
+
+    /// Consume the renderer, returning the underlying writer.
+    ///
+    /// Unlike [`StreamdownRenderer::finish`], buffered content is discarded.
+    pub fn into_inner(self) -> W {
+        self.renderer.into_inner()
+    }
+
+    /// Apply the latest width reported by the resize watcher, if any.
+    fn apply_resize(&mut self) {
+        #[cfg(all(feature = "resize", target_os = "linux"))]
+        if let Some(width) = self.resize_watcher.as_ref().and_then(ResizeWatcher::width) {
+            self.renderer.set_width(width);
+        }
+    }
+
+    /// Render the complete line at `range` of the line buffer.
+    fn render_buffered_line(&mut self, range: Range<usize>) -> io::Result<()> {
+        self.apply_resize();
+        self.renderer.clear_preview()?;
+        self.renderer.set_source_line(self.input_lines);
+        self.input_lines += 1;
+        let line = &self.line_buffer[range];
+        render_line(&mut self.parser, &mut self.renderer, &mut self.hooks, line)
+    }
+
+    /// Render the buffered partial line as if it were complete.
+    ///
+    /// Complete lines left over by a failed push are rendered first.
+    fn render_partial_line(&mut self) -> io::Result<()> {
+        self.apply_resize();
+        self.renderer.clear_preview()?;
+        let buffer = std::mem::take(&mut self.line_buffer);
+        self.scanned = 0;
+        for line in buffer.split_terminator('\n') {
+            self.renderer.set_source_line(self.input_lines);
+            self.input_lines += 1;
+            render_line(&mut self.parser, &mut self.renderer, &mut self.hooks, line)?;
         }
+        Ok(())
+    }
+
+    /// Render the buffered partial line and close any open blocks.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidData`] if the byte stream ended in the
+    /// middle of a UTF-8 sequence.
+    pub(crate) fn render_remaining(&mut self) -> io::Result<()> {
+        self.render_partial_line()?;
         for event in self.parser.finalize() {
-            self.renderer.render_event(&event)?;
+            dispatch(&mut self.hooks, &mut self.renderer, &event)?;
+        }
+
+        if !self.utf8_pending.is_empty() {
+            let bytes = std::mem::take(&mut self.utf8_pending);
+            let offset = self.bytes_pushed - bytes.len();
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                Error::InvalidUtf8 { offset, bytes },
+            ));
         }
         Ok(())
     }
+
+    /// Get a mutable reference to the underlying writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        self.renderer.get_mut()
+    }
+}
+
+/// Repair, parse, and render a single complete line.
+pub(crate) fn render_line<W: Write>(
+    parser: &mut Parser,
+    renderer: &mut Renderer<W>,
+    hooks: &mut [Box<dyn EventHook>],
+    line: &str,
+) -> io::Result<()> {
+    for repaired in repair_line(line, parser.state()) {
+        for event in parser.parse_line(&repaired) {
+            dispatch(hooks, renderer, &event)?;
+        }
+    }
+    Ok(())
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(renderer: &mut StreamdownRenderer<Vec<u8>>) -> String {
+        String::from_utf8(renderer.get_mut().clone()).unwrap()
+    }
+
+    /// Writer that records output and flushes, and fails every write while
+    /// `fail` is set.
+    #[derive(Default)]
+    struct TestWriter {
+        fail: bool,
+        written: Vec<u8>,
+        flushes: usize,
+    }
+
+    impl Write for TestWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.fail {
+                return Err(io::Error::new(io::ErrorKind::BrokenPipe, "closed"));
+            }
+            self.written.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.flushes += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_push_large_chunk_keeps_only_partial_line() {
+        let mut renderer = StreamdownRenderer::new(Vec::new(), 80);
+        let chunk: String = (0..1000).map(|i| format!("line {}\n", i)).collect();
+        renderer.push(&chunk).unwrap();
+        renderer.push("partial").unwrap();
+        assert_eq!(renderer.line_buffer, "partial");
+        assert_eq!(renderer.input_lines, 1000);
+
+        let output = output(&mut renderer);
+        assert!(output.contains("line 0"));
+        assert!(output.contains("line 999"));
+    }
+
+    #[test]
+    fn test_push_failure_drops_only_consumed_lines() {
+        let mut renderer = StreamdownRenderer::new(TestWriter::default(), 80);
+        renderer.get_mut().fail = true;
+        assert!(renderer.push("one\ntwo\n").is_err());
+
+        renderer.get_mut().fail = false;
+        renderer.push("three\n").unwrap();
+        assert!(renderer.line_buffer.is_empty());
+        assert_eq!(renderer.input_lines, 3);
+
+        let output = String::from_utf8(renderer.get_mut().written.clone()).unwrap();
+        assert!(!output.contains("one"));
+        assert!(output.contains("two"));
+        assert!(output.contains("three"));
+    }
+
+    #[test]
+    fn test_push_newline_split_across_tokens() {
+        let mut renderer = StreamdownRenderer::new(Vec::new(), 80);
+        for token in ["fir", "st\nsec", "ond", "\n", "\nthird\n"] {
+            renderer.push(token).unwrap();
+        }
+        assert!(renderer.line_buffer.is_empty());
+        assert_eq!(renderer.input_lines, 4);
+        let output = output(&mut renderer);
+        assert!(output.contains("first"));
+        assert!(output.contains("second"));
+        assert!(output.contains("third"));
+    }
+
+    #[test]
+    fn test_stalled_stream_is_flushed() {
+        for policy in [FlushPolicy::Line, FlushPolicy::Block] {
+            let mut renderer = StreamdownRenderer::new(TestWriter::default(), 80);
+            renderer.set_flush_policy(policy);
+            renderer.push("- item\n").unwrap();
+            assert_eq!(renderer.get_mut().flushes, 1, "{:?}", policy);
+        }
+
+        let mut renderer = StreamdownRenderer::new(TestWriter::default(), 80);
+        renderer.set_flush_policy(FlushPolicy::Manual);
+        renderer.push("- item\n").unwrap();
+        assert_eq!(renderer.get_mut().flushes, 0);
+    }
+
+    #[test]
+    fn test_interval_limits_flushes_across_pushes() {
+        let mut renderer = StreamdownRenderer::new(TestWriter::default(), 80);
+        renderer.set_flush_policy(FlushPolicy::Interval(std::time::Duration::from_secs(3600)));
+        for token in ["- one", "\n", "- two", "\n", "- three\n"] {
+            renderer.push(token).unwrap();
+        }
+        assert_eq!(renderer.get_mut().flushes, 0);
+        renderer.end_message().unwrap();
+        assert_eq!(renderer.get_mut().flushes, 1);
+    }
+
+    #[test]
+    fn test_block_policy_waits_for_code_block_end() {
+        let mut renderer = StreamdownRenderer::new(TestWriter::default(), 80);
+        renderer.set_flush_policy(FlushPolicy::Block);
+        for token in ["```rust\n", "fn main() {\n", "}\n"] {
+            renderer.push(token).unwrap();
+        }
+        assert_eq!(renderer.get_mut().flushes, 0);
+        renderer.push("```\n").unwrap();
+        assert_eq!(renderer.get_mut().flushes, 1);
+    }
+
+    #[test]
+    fn test_hook_sees_every_event() {
+        use std::sync::{Arc, Mutex};
+
+        struct Recorder(Arc<Mutex<Vec<String>>>);
+
+        impl EventHook for Recorder {
+            fn before_event(&mut self, event: &ParseEvent) -> HookAction {
+                self.0.lock().unwrap().push(format!("{:?}", event));
+                HookAction::Continue
+            }
+        }
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut renderer = StreamdownRenderer::new(Vec::new(), 80);
+        renderer.add_hook(Recorder(events.clone()));
+        renderer.push("```rust\nfn main() {}\n```\n").unwrap();
+        renderer.finish().unwrap();
+
+        let events = events.lock().unwrap();
+        assert!(events.iter().any(|e| e.starts_with("CodeBlockStart")));
+        assert!(events.iter().any(|e| e == "CodeBlockEnd"));
+    }
+
+    #[test]
+    fn test_take_code_blocks() {
+        let mut renderer = StreamdownRenderer::new(Vec::new(), 80);
+        renderer
+            .push("Intro\n\n```rust\nfn main() {\n}\n```\n\n```\n\n  indented\n```\n")
+            .unwrap();
+        renderer.end_message().unwrap();
+
+        let blocks = renderer.take_code_blocks();
+        assert_eq!(blocks.len(), 2);
+
+        assert_eq!(blocks[0].index, 0);
+        assert_eq!(blocks[0].language.as_deref(), Some("rust"));
+        assert_eq!(blocks[0].source, "fn main() {\n}");
+        assert_eq!(blocks[0].lines, 3..5);
+
+        assert_eq!(blocks[1].index, 1);
+        assert_eq!(blocks[1].language, None);
+        assert_eq!(blocks[1].source, "\n  indented");
+        assert_eq!(blocks[1].lines, 8..10);
+
+        assert!(renderer.take_code_blocks().is_empty());
+    }
+
+    fn numbered_block(lines: usize) -> String {
+        let code: String = (1..=lines).map(|i| format!("line {}\n", i)).collect();
+        format!("```\n{}```\nAfter\n", code)
+    }
+
+    #[test]
+    fn test_collapsed_code_block() {
+        let config = RendererConfig::new().code_collapse(2, 2);
+        let mut renderer = StreamdownRenderer::from_config(Vec::new(), config.width(40));
+        renderer.push(&numbered_block(10)).unwrap();
+        let source = renderer.take_code_blocks().remove(0).source;
+        assert_eq!(source.lines().count(), 10);
+
+        let output = String::from_utf8(renderer.finish().unwrap()).unwrap();
+        let output = strip_ansi_escapes::strip_str(output);
+        insta::assert_snapshot!(output, @r"
+        line 1
+        line 2
+        … 6 more lines
+        line 9
+        line 10
+        After
+        ");
+    }
+
+    #[test]
+    fn test_short_code_block_is_not_collapsed() {
+        let config = RendererConfig::new().code_collapse(2, 2);
+        let output = render_plain(config, &numbered_block(4));
+        assert!(output.contains("line 3"));
+        assert!(!output.contains("more line"));
+    }
+
+    #[test]
+    fn test_interrupt_writes_collapsed_tail() {
+        let config = RendererConfig::new().width(40).code_collapse(1, 1);
+        let mut renderer = StreamdownRenderer::from_config(Vec::new(), config);
+        renderer.push("```\none\ntwo\nthree\n").unwrap();
+        renderer.interrupt().unwrap();
+        let output = strip_ansi_escapes::strip_str(output(&mut renderer));
+        assert!(output.contains("one\n… 1 more line\nthree\n"));
+    }
+
+    #[test]
+    fn test_shell_session_commands() {
+        let mut renderer = StreamdownRenderer::new(Vec::new(), 80);
+        renderer
+            .push("```console\n$ cargo test \\\n  --all\nok\n$ ls\nsrc\n```\n")
+            .unwrap();
+        let blocks = renderer.take_code_blocks();
+        assert_eq!(blocks[0].commands, vec!["cargo test \\\n  --all", "ls"]);
+    }
+
+    #[test]
+    fn test_code_block_indices_restart_per_message() {
+        let mut renderer = StreamdownRenderer::new(Vec::new(), 80);
+        renderer.push("```\na\n```\n").unwrap();
+        renderer.end_message().unwrap();
+        renderer.push("```\nb\n```\n").unwrap();
+        renderer.end_message().unwrap();
+
+        let blocks = renderer.take_code_blocks();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[1].index, 0);
+        assert_eq!(blocks[1].lines, 1..2);
+    }
+
+    fn render_plain(config: RendererConfig, markdown: &str) -> String {
+        let mut renderer = StreamdownRenderer::from_config(Vec::new(), config.width(30));
+        renderer.push(markdown).unwrap();
+        let output = renderer.finish().unwrap();
+        strip_ansi_escapes::strip_str(String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn test_framed_code_block() {
+        let config = RendererConfig::new().code_block_style(CodeBlockStyle::Framed);
+        let output = render_plain(config, "Intro\n```rust\nfn main() {}\n```\nAfter\n");
+        insta::assert_snapshot!(output, @r"
+        Intro
+        ── rust ──────────────────────
+        fn main() {}
+        ──────────────────────────────
+        After
+        ");
+    }
+
+    #[test]
+    fn test_framed_code_block_without_language() {
+        let config = RendererConfig::new().code_block_style(CodeBlockStyle::Framed);
+        let output = render_plain(config, "```\nplain\n```\n");
+        assert!(output.starts_with(&format!("{}\nplain\n", "─".repeat(30))));
+    }
+
+    #[test]
+    fn test_interrupt_closes_frame() {
+        let config = RendererConfig::new()
+            .width(30)
+            .code_block_style(CodeBlockStyle::Framed);
+        let mut renderer = StreamdownRenderer::from_config(Vec::new(), config);
+        renderer.push("```rust\nfn main() {\n").unwrap();
+        renderer.interrupt().unwrap();
+        let output = strip_ansi_escapes::strip_str(output(&mut renderer));
+        assert!(output.contains(&format!("{}\n[interrupted]", "─".repeat(30))));
+    }
+
+    #[test]
+    fn test_code_block_title() {
+        let config = RendererConfig::new().code_block_style(CodeBlockStyle::Framed);
+        let output = render_plain(config, "```rust:src/main.rs\nfn main() {}\n```\n");
+        assert!(output.starts_with("── src/main.rs ───"));
+
+        // Goes through the parser, so this checks that it passes the whole
+        // info string on (see `CodeBlock::new`)
+        let markdown = "```python title=\"app.py\"\npass\n```\n";
+        let output = render_plain(RendererConfig::new(), markdown);
+        assert!(output.starts_with("app.py\npass\n"));
+    }
+
+    #[test]
+    fn test_unframed_code_block_by_default() {
+        let output = render_plain(RendererConfig::new(), "```rust\nfn main() {}\n```\n");
+        assert!(!output.contains('─'));
+    }
+
+    #[test]
+    fn test_copy_safe_code_without_padding() {
+        let markdown = "```bash\nls -la\n```\n";
+        let output = render_plain(RendererConfig::new().padding(2), markdown);
+        assert!(output.lines().any(|line| line == "  ls -la"));
+
+        let config = RendererConfig::new().padding(2).copy_safe_code(true);
+        let output = render_plain(config, markdown);
+        assert!(output.lines().any(|line| line == "ls -la"));
+    }
+
+    #[test]
+    fn test_code_clipboard() {
+        let config = RendererConfig::new().width(30).code_clipboard(true);
+        let mut renderer = StreamdownRenderer::from_config(Vec::new(), config);
+        renderer.push("```bash\nls -la\n```\nDone\n").unwrap();
+        let output = output(&mut renderer);
+        assert!(output.contains("\x1b]52;c;bHMgLWxh\x07"));
+        assert!(output.contains("Done"));
+    }
+
+    #[test]
+    fn test_push_bytes_split_code_point() {
+        let mut renderer = StreamdownRenderer::new(Vec::new(), 80);
+        let bytes = "héllo wörld\n".as_bytes();
+        // Split inside the two-byte 'é'
+        renderer.push_bytes(&bytes[..2]).unwrap();
+        renderer.push_bytes(&bytes[2..]).unwrap();
+        assert!(output(&mut renderer).contains("héllo wörld"));
+    }
+
+    #[test]
+    fn test_push_bytes_one_byte_at_a_time() {
+        let mut renderer = StreamdownRenderer::new(Vec::new(), 80);
+        for byte in "日本語 🎉\n".as_bytes() {
+            renderer.push_bytes(std::slice::from_ref(byte)).unwrap();
+        }
+        assert!(output(&mut renderer).contains("日本語 🎉"));
+    }
+
+    #[test]
+    fn test_push_bytes_invalid_sequence() {
+        let mut renderer = StreamdownRenderer::new(Vec::new(), 80);
+        renderer.push_bytes(b"ok ").unwrap();
+        let err = renderer.push_bytes(b"bad \xff rest\n").unwrap_err();
+        match err {
+            Error::InvalidUtf8 { offset, bytes } => {
+                assert_eq!(offset, 7);
+                assert_eq!(bytes, vec![0xff]);
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+        // Valid text around the invalid byte is still rendered
+        assert!(output(&mut renderer).contains("rest"));
+    }
+
+    #[test]
+    fn test_end_message_resets_for_next_message() {
+        let mut renderer = StreamdownRenderer::new(Vec::new(), 80);
+        renderer.push("```rust\nfn main() {}\n").unwrap();
+        let stats = renderer.end_message().unwrap();
+        assert_eq!(stats.input_lines, 2);
+        assert_eq!(stats.code_blocks, 1);
+        assert!(!renderer.parser.state().is_in_code());
+
+        renderer.push("Second message\n").unwrap();
+        let stats = renderer.end_message().unwrap();
+        assert_eq!(stats.input_lines, 1);
+        assert_eq!(stats.code_blocks, 0);
+        assert!(stats.bytes_written > 0);
+        assert!(output(&mut renderer).contains("Second message"));
+    }
+
+    #[test]
+    fn test_end_message_failure_discards_pending_input() {
+        let mut renderer = StreamdownRenderer::new(TestWriter::default(), 80);
+        renderer.push("partial").unwrap();
+        renderer.push_bytes(&"é".as_bytes()[..1]).unwrap();
+        renderer.get_mut().fail = true;
+        assert!(renderer.end_message().is_err());
+
+        renderer.get_mut().fail = false;
+        renderer.push_bytes(b"next\n").unwrap();
+        let output = String::from_utf8(renderer.get_mut().written.clone()).unwrap();
+        assert!(output.contains("next"));
+        assert!(!output.contains("partial"));
+    }
+
+    #[test]
+    fn test_end_message_renders_partial_line() {
+        let mut renderer = StreamdownRenderer::new(Vec::new(), 80);
+        renderer.push("no trailing newline").unwrap();
+        let stats = renderer.end_message().unwrap();
+        assert_eq!(stats.input_lines, 1);
+        assert!(output(&mut renderer).contains("no trailing newline"));
+    }
+
+    #[test]
+    fn test_interrupt_mid_code_block() {
+        let mut renderer = StreamdownRenderer::new(Vec::new(), 80);
+        renderer.push("```rust\nfn main() {\n    let x").unwrap();
+        let stats = renderer.interrupt().unwrap();
+        assert_eq!(stats.input_lines, 3);
+        assert!(!renderer.parser.state().is_in_code());
+        assert!(output(&mut renderer).contains("[interrupted]"));
+
+        // The renderer is reusable after an interrupt
+        renderer.push("Next\n").unwrap();
+        assert!(output(&mut renderer).contains("Next"));
+    }
+
+    #[test]
+    fn test_interrupt_keeps_partial_code_block() {
+        let mut renderer = StreamdownRenderer::new(Vec::new(), 80);
+        renderer.push("```rust\nfn main() {\n    let x").unwrap();
+        renderer.interrupt().unwrap();
+
+        let blocks = renderer.take_code_blocks();
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].language.as_deref(), Some("rust"));
+        assert_eq!(blocks[0].source, "fn main() {\n    let x");
+    }
+
+    #[test]
+    fn test_finish_returns_writer() {
+        let mut renderer = StreamdownRenderer::new(Vec::new(), 80);
+        renderer.push("Hello\n").unwrap();
+        let output = renderer.finish().unwrap();
+        assert!(String::from_utf8(output).unwrap().contains("Hello"));
+    }
+
+    #[test]
+    fn test_finish_with_incomplete_sequence() {
+        let mut renderer = StreamdownRenderer::new(Vec::new(), 80);
+        renderer.push_bytes(&"é".as_bytes()[..1]).unwrap();
+        let err = renderer.finish().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
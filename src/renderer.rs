@@ -1,36 +1,81 @@
 //! Main renderer that handles all parse events.
 
+use std::collections::VecDeque;
 use std::io::{self, Write};
+use std::time::Instant;
 
 use streamdown_parser::ParseEvent;
 
-use crate::code::CodeHighlighter;
+use crate::code::{
+    CodeBlockStyle, CodeCollapse, CodeHighlighter, CodeOverflow, HighlightAssets,
+};
+use crate::code_block::CodeBlock;
+use crate::config::RendererConfig;
+use crate::flush::FlushPolicy;
 use crate::heading::render_heading;
 use crate::inline::{render_inline_content, render_inline_elements};
 use crate::list::{render_list_item, ListState};
+use crate::session::{is_session_label, session_commands};
+use crate::stats::RenderStats;
 use crate::table::render_table;
 use crate::theme::Theme;
+use crate::utils::{osc52_copy, truncate_visible};
 use crate::style::InlineStyler;
+use streamdown_ansi::utils::visible_length;
 use streamdown_render::text::text_wrap;
 
+const RESET: &str = "\x1b[0m";
+
+/// Lines of the open code block held back by [`CodeCollapse`].
+#[derive(Default)]
+struct CollapsedLines {
+    /// Lines of the block seen so far.
+    lines: usize,
+    /// Lines dropped between the head and the tail.
+    hidden: usize,
+    /// Rendered output of the last lines, at most `tail` of them.
+    tail: VecDeque<Vec<String>>,
+}
+
 /// Main renderer for markdown events.
 pub struct Renderer<W: Write> {
     writer: W,
     width: usize,
+    // Layout
+    max_width: Option<usize>,
+    padding: usize,
     theme: Theme,
     // Code highlighting
     highlighter: CodeHighlighter,
-    current_language: Option<String>,
+    code_block_style: CodeBlockStyle,
+    code_collapse: Option<CodeCollapse>,
+    collapsed: CollapsedLines,
+    copy_safe_code: bool,
+    code_clipboard: bool,
     code_buffer: String,
+    // Code block extraction
+    open_code_block: Option<CodeBlock>,
+    code_blocks: Vec<CodeBlock>,
+    source_line: usize,
     // Table buffering
     table_rows: Vec<Vec<String>>,
     // Blockquote state
     in_blockquote: bool,
     blockquote_depth: usize,
+    in_think: bool,
     // List state
     list_state: ListState,
     // Column tracking
     column: usize,
+    // Whether the live preview of the in-progress line is on screen
+    preview_shown: bool,
+    // Statistics for the current message
+    stats: RenderStats,
+    // Flushing
+    flush_policy: FlushPolicy,
+    last_flush: Instant,
+    unflushed_line: bool,
+    unflushed_output: bool,
 }
 
 impl<W: Write> Renderer<W> {
@@ -39,24 +84,154 @@ impl<W: Write> Renderer<W> {
     }
 
     pub fn with_theme(writer: W, width: usize, theme: Theme) -> Self {
+        let mut highlighter = CodeHighlighter::default();
+        highlighter.apply_theme(&theme);
         Self {
             writer,
             width,
+            max_width: None,
+            padding: 0,
             theme,
-            highlighter: CodeHighlighter::default(),
-            current_language: None,
+            highlighter,
+            code_block_style: CodeBlockStyle::default(),
+            code_collapse: None,
+            collapsed: CollapsedLines::default(),
+            copy_safe_code: false,
+            code_clipboard: false,
             code_buffer: String::new(),
+            open_code_block: None,
+            code_blocks: Vec::new(),
+            source_line: 0,
             table_rows: Vec::new(),
             in_blockquote: false,
             blockquote_depth: 0,
+            in_think: false,
             list_state: ListState::default(),
             column: 0,
+            preview_shown: false,
+            stats: RenderStats::default(),
+            flush_policy: FlushPolicy::default(),
+            last_flush: Instant::now(),
+            unflushed_line: false,
+            unflushed_output: false,
+        }
+    }
+
+    /// Create a new renderer from a configuration.
+    pub fn from_config(writer: W, config: &RendererConfig) -> Self {
+        let mut theme = config.theme.clone().unwrap_or_default();
+        if let Some(code_theme) = &config.code_theme {
+            theme.code_theme = Some(code_theme.clone());
+        }
+        let mut renderer = Self::with_theme(writer, config.resolved_width(), theme);
+        renderer.max_width = config.max_width;
+        renderer.padding = config.padding;
+        renderer.flush_policy = config.flush_policy;
+        if let Some(assets) = &config.highlight_assets {
+            renderer.highlighter.set_assets(assets.clone());
         }
+        renderer.code_block_style = config.code_block_style;
+        renderer.code_collapse = config.code_collapse;
+        renderer.highlighter.set_background(config.code_background);
+        renderer.highlighter.set_line_numbers(config.code_line_numbers);
+        renderer.highlighter.set_diff_highlighting(config.diff_highlighting);
+        renderer.highlighter.set_overflow(config.code_overflow);
+        renderer.set_copy_safe_code(config.copy_safe_code);
+        renderer.code_clipboard = config.code_clipboard;
+        for (alias, language) in &config.language_aliases {
+            renderer.highlighter.add_language_alias(alias, language);
+        }
+        renderer
+    }
+
+    /// Set the terminal width.
+    ///
+    /// Applies to everything rendered afterwards, including table rows that
+    /// are still buffered.
+    pub fn set_width(&mut self, width: usize) {
+        self.width = width;
+    }
+
+    /// Get the terminal width.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Use specific syntax and theme sets for code highlighting.
+    pub fn set_highlight_assets(&mut self, assets: HighlightAssets) {
+        self.highlighter.set_assets(assets);
+    }
+
+    /// Highlight code fences labeled `alias` as `language`.
+    pub fn add_language_alias(&mut self, alias: &str, language: &str) {
+        self.highlighter.add_language_alias(alias, language);
+    }
+
+    /// Set how code blocks are set off from the surrounding text.
+    pub fn set_code_block_style(&mut self, style: CodeBlockStyle) {
+        self.code_block_style = style;
+    }
+
+    /// Fill code blocks with the code theme's background color.
+    pub fn set_code_background(&mut self, enabled: bool) {
+        self.highlighter.set_background(enabled);
+    }
+
+    /// Show line numbers in code blocks.
+    pub fn set_code_line_numbers(&mut self, enabled: bool) {
+        self.highlighter.set_line_numbers(enabled);
+    }
+
+    /// Highlight the changed code in diff blocks.
+    pub fn set_diff_highlighting(&mut self, enabled: bool) {
+        self.highlighter.set_diff_highlighting(enabled);
+    }
+
+    /// Collapse long code blocks, or show them in full with `None`.
+    ///
+    /// Lines past the head are held back until the block ends, since only
+    /// then is it known which lines form the tail.
+    pub fn set_code_collapse(&mut self, collapse: Option<CodeCollapse>) {
+        self.code_collapse = collapse;
+    }
+
+    /// Set how code lines wider than the terminal are displayed.
+    pub fn set_code_overflow(&mut self, overflow: CodeOverflow) {
+        self.highlighter.set_overflow(overflow);
+    }
+
+    /// Render each code line unchanged on a single output line, without
+    /// margins, line numbers or background padding, so it can be copied from
+    /// the terminal as-is.
+    pub fn set_copy_safe_code(&mut self, enabled: bool) {
+        self.copy_safe_code = enabled;
+        self.highlighter.set_copy_safe(enabled);
+    }
+
+    /// Copy each completed code block to the clipboard with OSC 52.
+    pub fn set_code_clipboard(&mut self, enabled: bool) {
+        self.code_clipboard = enabled;
+    }
+
+    /// Clamp the content width to a maximum readable width.
+    pub fn set_max_width(&mut self, max_width: Option<usize>) {
+        self.max_width = max_width;
+    }
+
+    /// Indent all output by the given number of columns.
+    pub fn set_padding(&mut self, padding: usize) {
+        self.padding = padding;
+    }
+
+    /// Set when the writer is flushed.
+    pub fn set_flush_policy(&mut self, policy: FlushPolicy) {
+        self.flush_policy = policy;
     }
 
     /// Set a new theme.
     #[allow(dead_code)]
     pub fn set_theme(&mut self, theme: Theme) {
+        self.highlighter.apply_theme(&theme);
         self.theme = theme;
     }
 
@@ -66,13 +241,107 @@ impl<W: Write> Renderer<W> {
         &self.theme
     }
 
-    /// Calculate the left margin based on blockquote depth.
+    /// Get a mutable reference to the underlying writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Consume the renderer, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Take the statistics collected since the last call, resetting them.
+    pub fn take_stats(&mut self) -> RenderStats {
+        std::mem::take(&mut self.stats)
+    }
+
+    /// Reset all block state so the next event starts a fresh message.
+    ///
+    /// Buffered table rows are discarded; render `TableEnd` first to keep them.
+    pub fn reset(&mut self) {
+        self.code_buffer.clear();
+        self.open_code_block = None;
+        self.collapsed = CollapsedLines::default();
+        self.highlighter.end_block();
+        self.source_line = 0;
+        self.table_rows.clear();
+        self.in_blockquote = false;
+        self.blockquote_depth = 0;
+        self.in_think = false;
+        self.list_state.reset();
+        self.column = 0;
+        self.preview_shown = false;
+    }
+
+    /// Set the input line (counted from 0 within the message) that the
+    /// following events were parsed from.
+    pub fn set_source_line(&mut self, line: usize) {
+        self.source_line = line;
+    }
+
+    /// Get the code blocks completed so far.
+    pub fn code_blocks(&self) -> &[CodeBlock] {
+        &self.code_blocks
+    }
+
+    /// Take the code blocks completed so far.
+    ///
+    /// Block indices restart with every message, so take the blocks after
+    /// each message to keep them apart.
+    pub fn take_code_blocks(&mut self) -> Vec<CodeBlock> {
+        std::mem::take(&mut self.code_blocks)
+    }
+
+    /// Flush the underlying writer.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.last_flush = Instant::now();
+        self.unflushed_line = false;
+        self.unflushed_output = false;
+        self.writer.flush()
+    }
+
+    /// Flush output still waiting for the flush policy, as far as the policy
+    /// allows.
+    ///
+    /// Called once a pushed chunk has been rendered, so output is not held
+    /// back while the stream stalls. [`FlushPolicy::Interval`] still limits
+    /// the rate, and [`FlushPolicy::Block`] waits for an open code block to
+    /// end.
+    pub fn flush_pending(&mut self) -> io::Result<()> {
+        let allowed = match self.flush_policy {
+            FlushPolicy::Event | FlushPolicy::Line => true,
+            FlushPolicy::Block => self.open_code_block.is_none(),
+            FlushPolicy::Interval(interval) => self.last_flush.elapsed() >= interval,
+            FlushPolicy::Manual => false,
+        };
+        if self.unflushed_output && allowed {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Global left padding applied to every line.
+    fn padding(&self) -> String {
+        " ".repeat(self.padding)
+    }
+
+    /// Calculate the left margin based on padding and blockquote depth.
     fn left_margin(&self) -> String {
         if self.in_blockquote {
             let border = self.theme.blockquote_border.apply("│").to_string();
-            format!("{} ", border).repeat(self.blockquote_depth)
+            format!("{}{}", self.padding(), format!("{} ", border).repeat(self.blockquote_depth))
         } else {
-            String::new()
+            self.padding()
+        }
+    }
+
+    /// Calculate the content width, excluding padding and clamped to the max width.
+    fn content_width(&self) -> usize {
+        let width = self.width.saturating_sub(self.padding);
+        match self.max_width {
+            Some(max_width) => width.min(max_width),
+            None => width,
         }
     }
 
@@ -83,16 +352,44 @@ impl<W: Write> Renderer<W> {
         } else {
             0
         };
-        self.width.saturating_sub(margin_width)
+        self.content_width().saturating_sub(margin_width)
     }
 
     fn write(&mut self, s: &str) -> io::Result<()> {
+        self.stats.bytes_written += s.len();
+        self.unflushed_output = true;
+        match s.rfind('\n') {
+            Some(pos) => self.column = visible_length(&s[pos + 1..]),
+            None => self.column += visible_length(s),
+        }
         write!(self.writer, "{}", s)
     }
 
+    /// Write inline content, starting each output line at the left margin.
+    fn write_inline(&mut self, s: &str) -> io::Result<()> {
+        if self.column == 0 && !s.is_empty() {
+            let margin = self.left_margin();
+            self.write(&margin)?;
+        }
+        self.write(s)
+    }
+
+    /// Write an OSC 52 sequence copying text to the clipboard. The sequence
+    /// is invisible, so the column is left alone.
+    fn write_clipboard(&mut self, text: &str) -> io::Result<()> {
+        let sequence = osc52_copy(text);
+        self.stats.bytes_written += sequence.len();
+        self.unflushed_output = true;
+        write!(self.writer, "{}", sequence)
+    }
+
     fn writeln(&mut self, s: &str) -> io::Result<()> {
+        self.stats.bytes_written += s.len() + 1;
+        self.stats.output_lines += s.matches('\n').count() + 1;
         writeln!(self.writer, "{}", s)?;
         self.column = 0;
+        self.unflushed_line = true;
+        self.unflushed_output = true;
         Ok(())
     }
 
@@ -101,14 +398,160 @@ impl<W: Write> Renderer<W> {
             return Ok(());
         }
         let rows = std::mem::take(&mut self.table_rows);
+        self.stats.tables += 1;
         let margin = self.left_margin();
-        let lines = render_table(&rows, &margin, &self.theme, self.width);
+        // The margin includes the padding, so give the table room for it
+        let max_width = self.content_width() + self.padding;
+        let lines = render_table(&rows, &margin, &self.theme, max_width);
         for line in lines {
             self.writeln(&line)?;
         }
         Ok(())
     }
 
+    /// Render a live preview of an incomplete line.
+    ///
+    /// The preview is styled on a best-effort basis (inline formatting only) and
+    /// is erased by [`Renderer::clear_preview`] once the complete line arrives.
+    /// It is cut to one column less than the width, so it takes a single row
+    /// as long as the terminal is at least that wide.
+    pub fn render_preview(&mut self, partial: &str, in_code: bool) -> io::Result<()> {
+        self.clear_preview()?;
+
+        // Table rows are buffered until the table ends, so previewing them would
+        // only make the row flash and disappear.
+        if partial.is_empty() || (!in_code && partial.trim_start().starts_with('|')) {
+            return Ok(());
+        }
+
+        let content = if in_code {
+            partial.to_string()
+        } else {
+            render_inline_content(partial, &self.theme)
+        };
+        let line = format!("{}{}", self.left_margin(), content);
+        let line = truncate_visible(&line, self.width.saturating_sub(1));
+        self.write(&format!("{}{}", line, RESET))?;
+        self.preview_shown = true;
+        self.flush()
+    }
+
+    /// Erase the live preview, leaving the cursor at the start of its row.
+    pub fn clear_preview(&mut self) -> io::Result<()> {
+        if !self.preview_shown {
+            return Ok(());
+        }
+        // Cursor movement is invisible, so write it past the column tracking
+        let erase = "\r\x1b[K";
+        self.stats.bytes_written += erase.len();
+        self.unflushed_output = true;
+        self.writer.write_all(erase.as_bytes())?;
+        self.column = 0;
+        self.preview_shown = false;
+        Ok(())
+    }
+
+    /// Close everything left open by an interrupted stream.
+    ///
+    /// Flushes buffered table rows, closes think block framing, resets all
+    /// ANSI attributes, and prints the themed "interrupted" marker. All block
+    /// state is reset afterwards.
+    pub fn interrupt(&mut self) -> io::Result<()> {
+        self.clear_preview()?;
+        self.write(RESET)?;
+        if self.column > 0 {
+            self.writeln("")?;
+        }
+        self.flush_table()?;
+        self.write_collapsed_lines()?;
+        if self.in_framed_code_block() {
+            let footer = self.code_block_footer();
+            self.writeln(&footer)?;
+        }
+        let padding = self.padding();
+        if self.in_think {
+            let border = self.theme.think_border.apply("└");
+            self.writeln(&format!("{}{}", padding, border))?;
+        }
+        let marker = self.theme.interrupted.apply("[interrupted]");
+        self.writeln(&format!("{}{}", padding, marker))?;
+        self.reset();
+        self.flush()
+    }
+
+    /// Write the lines held back by code collapsing: the marker counting the
+    /// hidden lines, then the tail.
+    fn write_collapsed_lines(&mut self) -> io::Result<()> {
+        let collapsed = std::mem::take(&mut self.collapsed);
+        if collapsed.hidden > 0 {
+            let noun = if collapsed.hidden == 1 { "line" } else { "lines" };
+            let text = format!("… {} more {}", collapsed.hidden, noun);
+            let marker = self.theme.code_collapsed.apply(&text);
+            self.writeln(&format!("{}{}", self.left_margin(), marker))?;
+        }
+        for rendered in collapsed.tail.into_iter().flatten() {
+            self.writeln(&rendered)?;
+        }
+        Ok(())
+    }
+
+    /// Check if a code block is open and drawn with a frame.
+    fn in_framed_code_block(&self) -> bool {
+        self.open_code_block.is_some() && self.code_block_style == CodeBlockStyle::Framed
+    }
+
+    /// Render the header of a code block.
+    ///
+    /// Framed blocks get a top border labeled with the block's title or
+    /// language. Unframed blocks only get a header line if they have a title.
+    fn code_block_header(&self) -> Option<String> {
+        let block = self.open_code_block.as_ref()?;
+        let label = block.title.as_deref().or(block.language.as_deref());
+        if self.code_block_style != CodeBlockStyle::Framed {
+            let title = self.theme.code_block_lang.apply(block.title.as_deref()?);
+            return Some(format!("{}{}", self.left_margin(), title));
+        }
+        let Some(label) = label else {
+            return Some(self.code_block_footer());
+        };
+        let border = &self.theme.code_block_border;
+        let rest = self.current_width().saturating_sub(visible_length(label) + 4);
+        Some(format!(
+            "{}{}{}{}",
+            self.left_margin(),
+            border.apply("── "),
+            self.theme.code_block_lang.apply(label),
+            border.apply(&format!(" {}", "─".repeat(rest)))
+        ))
+    }
+
+    /// Render the bottom border of a framed code block.
+    fn code_block_footer(&self) -> String {
+        let rule = "─".repeat(self.current_width());
+        format!("{}{}", self.left_margin(), self.theme.code_block_border.apply(&rule))
+    }
+
+    /// Check if the flush policy calls for a flush after this event.
+    fn should_flush(&self, event: &ParseEvent) -> bool {
+        match self.flush_policy {
+            FlushPolicy::Event => true,
+            FlushPolicy::Line => self.unflushed_line,
+            FlushPolicy::Block => matches!(
+                event,
+                ParseEvent::Heading { .. }
+                    | ParseEvent::CodeBlockEnd
+                    | ParseEvent::ListEnd
+                    | ParseEvent::TableEnd
+                    | ParseEvent::BlockquoteEnd
+                    | ParseEvent::ThinkBlockEnd
+                    | ParseEvent::HorizontalRule
+                    | ParseEvent::EmptyLine
+            ),
+            FlushPolicy::Interval(interval) => self.last_flush.elapsed() >= interval,
+            FlushPolicy::Manual => false,
+        }
+    }
+
     /// Check if this event should reset a pending list.
     /// List continues only for ListItem, ListEnd, and EmptyLine/Newline events.
     fn should_reset_list(event: &ParseEvent) -> bool {
@@ -127,57 +570,57 @@ impl<W: Write> Renderer<W> {
         if Self::should_reset_list(event) {
             self.list_state.reset();
         }
+        self.stats.events += 1;
 
         match event {
             // === Inline elements ===
             ParseEvent::Text(text) => {
-                let styled = self.theme.text(text);
-                self.write(&styled)?;
-                self.column += styled.chars().count();
+                self.write_inline(&self.theme.text(text))?;
             }
 
             ParseEvent::InlineCode(code) => {
-                self.write(&self.theme.code(code))?;
+                self.write_inline(&self.theme.code(code))?;
             }
 
             ParseEvent::Bold(text) => {
-                self.write(&self.theme.bold(text))?;
+                self.write_inline(&self.theme.bold(text))?;
             }
 
             ParseEvent::Italic(text) => {
-                self.write(&self.theme.italic(text))?;
+                self.write_inline(&self.theme.italic(text))?;
             }
 
             ParseEvent::BoldItalic(text) => {
-                self.write(&self.theme.bold_italic(text))?;
+                self.write_inline(&self.theme.bold_italic(text))?;
             }
 
             ParseEvent::Underline(text) => {
-                self.write(&self.theme.underline(text))?;
+                self.write_inline(&self.theme.underline(text))?;
             }
 
             ParseEvent::Strikeout(text) => {
-                self.write(&self.theme.strikethrough(text))?;
+                self.write_inline(&self.theme.strikethrough(text))?;
             }
 
             ParseEvent::Link { text, url } => {
-                self.write(&self.theme.link(text, url))?;
+                self.write_inline(&self.theme.link(text, url))?;
             }
 
             ParseEvent::Image { alt, url } => {
-                self.write(&self.theme.image(alt, url))?;
+                self.write_inline(&self.theme.image(alt, url))?;
             }
 
             ParseEvent::Footnote(superscript) => {
-                self.write(&self.theme.footnote(superscript))?;
+                self.write_inline(&self.theme.footnote(superscript))?;
             }
 
             ParseEvent::Prompt(prompt) => {
-                self.write(prompt)?;
+                self.write_inline(prompt)?;
             }
 
             // === Block elements ===
             ParseEvent::Heading { level, content } => {
+                self.stats.headings += 1;
                 let margin = self.left_margin();
                 let width = self.current_width();
                 let lines = render_heading(*level, content, width, &margin, &self.theme);
@@ -187,31 +630,71 @@ impl<W: Write> Renderer<W> {
             }
 
             ParseEvent::CodeBlockStart { language, .. } => {
-                self.current_language = language.clone();
                 self.code_buffer.clear();
+                self.collapsed = CollapsedLines::default();
+                let block = CodeBlock::new(
+                    self.stats.code_blocks,
+                    language.as_deref(),
+                    self.source_line + 1,
+                );
+                self.highlighter.begin_block(block.language.as_deref());
+                self.highlighter.emphasize_lines(&block.emphasized_lines);
+                self.open_code_block = Some(block);
+                self.stats.code_blocks += 1;
+                if let Some(header) = self.code_block_header() {
+                    self.writeln(&header)?;
+                }
             }
 
             ParseEvent::CodeBlockLine(line) => {
-                if !self.code_buffer.is_empty() {
-                    self.code_buffer.push('\n');
-                }
                 self.code_buffer.push_str(line);
+                self.code_buffer.push('\n');
+                if let Some(block) = &mut self.open_code_block {
+                    block.lines.end = self.source_line + 1;
+                }
 
-                let margin = self.left_margin();
+                let margin = if self.copy_safe_code {
+                    String::new()
+                } else {
+                    self.left_margin()
+                };
                 let width = self.current_width();
-                let rendered_lines = self.highlighter.render_code_line(
-                    line,
-                    self.current_language.as_deref(),
-                    &margin,
-                    width,
-                );
-                for rendered in rendered_lines {
-                    self.writeln(&rendered)?;
+                let rendered_lines = self.highlighter.render_code_line(line, &margin, width);
+                match self.code_collapse {
+                    Some(collapse) if self.collapsed.lines >= collapse.head => {
+                        self.collapsed.tail.push_back(rendered_lines);
+                        if self.collapsed.tail.len() > collapse.tail {
+                            self.collapsed.tail.pop_front();
+                            self.collapsed.hidden += 1;
+                        }
+                    }
+                    _ => {
+                        for rendered in rendered_lines {
+                            self.writeln(&rendered)?;
+                        }
+                    }
                 }
+                self.collapsed.lines += 1;
             }
 
             ParseEvent::CodeBlockEnd => {
-                self.current_language = None;
+                self.write_collapsed_lines()?;
+                if self.in_framed_code_block() {
+                    let footer = self.code_block_footer();
+                    self.writeln(&footer)?;
+                }
+                if let Some(mut block) = self.open_code_block.take() {
+                    block.source = std::mem::take(&mut self.code_buffer);
+                    block.source.pop();
+                    if block.language.as_deref().is_some_and(is_session_label) {
+                        block.commands = session_commands(&block.source);
+                    }
+                    if self.code_clipboard {
+                        self.write_clipboard(&block.source)?;
+                    }
+                    self.code_blocks.push(block);
+                }
+                self.highlighter.end_block();
                 self.code_buffer.clear();
             }
 
@@ -277,18 +760,23 @@ impl<W: Write> Renderer<W> {
             }
 
             ParseEvent::ThinkBlockStart => {
-                self.writeln(&self.theme.think_border.apply("┌─ thinking ─").to_string())?;
+                let border = self.theme.think_border.apply("┌─ thinking ─");
+                self.writeln(&format!("{}{}", self.padding(), border))?;
+                self.in_think = true;
                 self.in_blockquote = true;
                 self.blockquote_depth = 1;
             }
 
             ParseEvent::ThinkBlockLine(text) => {
-                let border = self.theme.think_border.apply("│").to_string();
-                self.writeln(&format!("{} {}", border, self.theme.think.apply(text)))?;
+                let border = self.theme.think_border.apply("│");
+                let line = format!("{}{} {}", self.padding(), border, self.theme.think.apply(text));
+                self.writeln(&line)?;
             }
 
             ParseEvent::ThinkBlockEnd => {
-                self.writeln(&self.theme.think_border.apply("└").to_string())?;
+                let border = self.theme.think_border.apply("└");
+                self.writeln(&format!("{}{}", self.padding(), border))?;
+                self.in_think = false;
                 self.in_blockquote = false;
                 self.blockquote_depth = 0;
             }
@@ -303,10 +791,204 @@ impl<W: Write> Renderer<W> {
                 self.writeln("")?;
             }
             ParseEvent::InlineElements(elements) => {
-                self.write(&render_inline_elements(elements, &self.theme))?;
+                self.write_inline(&render_inline_elements(elements, &self.theme))?;
             }
         }
 
-        self.writer.flush()
+        if self.should_flush(event) {
+            self.flush()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(renderer: &Renderer<Vec<u8>>) -> String {
+        String::from_utf8(renderer.writer.clone()).unwrap()
+    }
+
+    /// Writer that counts flushes.
+    #[derive(Default)]
+    struct FlushCounter {
+        flushes: usize,
+    }
+
+    impl Write for FlushCounter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.flushes += 1;
+            Ok(())
+        }
+    }
+
+    fn count_flushes(policy: FlushPolicy, events: &[ParseEvent]) -> usize {
+        let mut renderer = Renderer::new(FlushCounter::default(), 80);
+        renderer.set_flush_policy(policy);
+        for event in events {
+            renderer.render_event(event).unwrap();
+        }
+        renderer.writer.flushes
+    }
+
+    fn paragraph_events() -> Vec<ParseEvent> {
+        vec![
+            ParseEvent::Text("one".into()),
+            ParseEvent::Text(" two".into()),
+            ParseEvent::Newline,
+            ParseEvent::Text("three".into()),
+            ParseEvent::Newline,
+            ParseEvent::EmptyLine,
+        ]
+    }
+
+    #[test]
+    fn test_flush_policy_event() {
+        assert_eq!(count_flushes(FlushPolicy::Event, &paragraph_events()), 6);
+    }
+
+    #[test]
+    fn test_flush_policy_line() {
+        assert_eq!(count_flushes(FlushPolicy::Line, &paragraph_events()), 3);
+    }
+
+    #[test]
+    fn test_flush_policy_block() {
+        assert_eq!(count_flushes(FlushPolicy::Block, &paragraph_events()), 1);
+    }
+
+    #[test]
+    fn test_flush_policy_manual() {
+        assert_eq!(count_flushes(FlushPolicy::Manual, &paragraph_events()), 0);
+    }
+
+    #[test]
+    fn test_flush_policy_interval() {
+        let policy = FlushPolicy::Interval(std::time::Duration::from_secs(3600));
+        assert_eq!(count_flushes(policy, &paragraph_events()), 0);
+        let policy = FlushPolicy::Interval(std::time::Duration::ZERO);
+        assert_eq!(count_flushes(policy, &paragraph_events()), 6);
+    }
+
+    #[test]
+    fn test_preview_is_written_immediately() {
+        let mut renderer = Renderer::new(Vec::new(), 80);
+        renderer.render_preview("Hello wor", false).unwrap();
+        assert!(output(&renderer).contains("Hello wor"));
+        assert!(renderer.preview_shown);
+    }
+
+    #[test]
+    fn test_clear_preview_erases_line() {
+        let mut renderer = Renderer::new(Vec::new(), 80);
+        renderer.render_preview("Hello", false).unwrap();
+        renderer.clear_preview().unwrap();
+        assert!(output(&renderer).ends_with("\r\x1b[K"));
+        assert!(!renderer.preview_shown);
+        assert_eq!(renderer.column, 0);
+    }
+
+    #[test]
+    fn test_interrupt_after_preview_has_no_blank_line() {
+        let mut renderer = Renderer::new(Vec::new(), 80);
+        renderer.render_preview("partial", false).unwrap();
+        renderer.interrupt().unwrap();
+        assert_eq!(output(&renderer).matches('\n').count(), 1);
+    }
+
+    #[test]
+    fn test_long_preview_is_cut_to_one_row() {
+        let mut renderer = Renderer::new(Vec::new(), 10);
+        renderer.render_preview(&"x".repeat(25), true).unwrap();
+        renderer.render_preview("**bold** text that runs on", false).unwrap();
+        renderer.clear_preview().unwrap();
+        let output = output(&renderer);
+        let previews: Vec<String> = output
+            .split("\r\x1b[K")
+            .map(strip_ansi_escapes::strip_str)
+            .collect();
+        assert_eq!(previews, vec!["x".repeat(9).as_str(), "bold text", ""]);
+        assert!(!output.contains("A\r"));
+    }
+
+    #[test]
+    fn test_clear_without_preview_is_noop() {
+        let mut renderer = Renderer::new(Vec::new(), 80);
+        renderer.clear_preview().unwrap();
+        assert!(output(&renderer).is_empty());
+    }
+
+    #[test]
+    fn test_interrupt_flushes_table_and_closes_think_block() {
+        let mut renderer = Renderer::new(Vec::new(), 80);
+        renderer.render_event(&ParseEvent::ThinkBlockStart).unwrap();
+        renderer
+            .render_event(&ParseEvent::TableHeader(vec!["Name".into(), "Value".into()]))
+            .unwrap();
+        renderer.interrupt().unwrap();
+
+        let output = output(&renderer);
+        assert!(output.contains("Name"));
+        assert!(output.contains("└"));
+        assert!(output.contains("[interrupted]"));
+        assert!(renderer.table_rows.is_empty());
+        assert!(!renderer.in_think);
+    }
+
+    #[test]
+    fn test_interrupt_ends_partial_line() {
+        let mut renderer = Renderer::new(Vec::new(), 80);
+        renderer.render_event(&ParseEvent::Text("partial".into())).unwrap();
+        renderer.interrupt().unwrap();
+        assert!(output(&renderer).contains(&format!("partial{}\n", RESET)));
+    }
+
+    #[test]
+    fn test_set_width_applies_to_buffered_table() {
+        let mut renderer = Renderer::new(Vec::new(), 120);
+        let long = "word ".repeat(10);
+        renderer
+            .render_event(&ParseEvent::TableHeader(vec![long.clone(), long]))
+            .unwrap();
+        renderer.set_width(40);
+        renderer.render_event(&ParseEvent::TableEnd).unwrap();
+
+        let output = output(&renderer);
+        assert!(!output.is_empty());
+        for line in output.lines() {
+            assert!(visible_length(line) <= 40, "line too wide: {:?}", line);
+        }
+    }
+
+    #[test]
+    fn test_padding_and_max_width() {
+        let config = RendererConfig::new().width(100).max_width(60).padding(4);
+        let mut renderer = Renderer::from_config(Vec::new(), &config);
+        assert_eq!(renderer.current_width(), 60);
+
+        renderer.render_event(&ParseEvent::HorizontalRule).unwrap();
+        let output = output(&renderer);
+        assert!(output.starts_with("    "));
+        assert_eq!(visible_length(output.trim_end_matches('\n')), 64);
+    }
+
+    #[test]
+    fn test_narrow_terminal_ignores_max_width() {
+        let config = RendererConfig::new().width(40).max_width(60).padding(2);
+        let renderer = Renderer::from_config(Vec::new(), &config);
+        assert_eq!(renderer.current_width(), 38);
+    }
+
+    #[test]
+    fn test_table_rows_are_not_previewed() {
+        let mut renderer = Renderer::new(Vec::new(), 80);
+        renderer.render_preview("| a | b", false).unwrap();
+        assert!(output(&renderer).is_empty());
+        assert!(!renderer.preview_shown);
     }
 }
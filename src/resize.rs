@@ -0,0 +1,60 @@
+//! Automatic width updates when the terminal is resized.
+
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use signal_hook::consts::SIGWINCH;
+use signal_hook::iterator::{Handle, Signals};
+
+use crate::utils::terminal_width;
+
+/// Tracks the width of the terminal attached to stdout by listening for
+/// `SIGWINCH` on a background thread.
+pub struct ResizeWatcher {
+    width: Arc<AtomicUsize>,
+    handle: Handle,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ResizeWatcher {
+    /// Start listening for resize signals.
+    pub fn new() -> io::Result<Self> {
+        let width = Arc::new(AtomicUsize::new(terminal_width().unwrap_or(0)));
+        let mut signals = Signals::new([SIGWINCH])?;
+        let handle = signals.handle();
+
+        let shared = Arc::clone(&width);
+        let thread = thread::spawn(move || {
+            for _ in signals.forever() {
+                if let Some(columns) = terminal_width() {
+                    shared.store(columns, Ordering::Relaxed);
+                }
+            }
+        });
+
+        Ok(Self {
+            width,
+            handle,
+            thread: Some(thread),
+        })
+    }
+
+    /// Get the latest known terminal width, if stdout is a terminal.
+    pub fn width(&self) -> Option<usize> {
+        match self.width.load(Ordering::Relaxed) {
+            0 => None,
+            columns => Some(columns),
+        }
+    }
+}
+
+impl Drop for ResizeWatcher {
+    fn drop(&mut self) {
+        self.handle.close();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,163 @@
+//! Classification of shell session lines into commands and output.
+//!
+//! Shell session blocks (```` ```console ````) mix commands behind a prompt
+//! with the output they produced. A command continues on the next line if it
+//! ends with a backslash, or if it follows a `$ ` or `# ` prompt and the next
+//! line starts with the `> ` continuation prompt. Anywhere else `> ` is a
+//! prompt of its own, as in PowerShell and REPL sessions.
+
+/// Fence labels rendered as shell sessions.
+const SESSION_LABELS: &[&str] = &["console", "shell-session", "sh-session", "terminal"];
+
+/// Prompts that start a command.
+const PROMPTS: &[&str] = &["$ ", "# ", "> "];
+
+/// Prompt of a continued shell command.
+const CONTINUATION_PROMPT: &str = "> ";
+
+/// Check if a fence label names a shell session.
+pub(crate) fn is_session_label(label: &str) -> bool {
+    SESSION_LABELS
+        .iter()
+        .any(|session| session.eq_ignore_ascii_case(label))
+}
+
+/// The kind of a shell session line.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum SessionLine<'a> {
+    /// A command, or a continuation of the previous one.
+    Command {
+        /// The prompt before the command, empty for continuations without one.
+        prompt: &'a str,
+        /// The command text after the prompt.
+        command: &'a str,
+        /// The line continues the previous command.
+        continued: bool,
+    },
+    /// Output of a command.
+    Output,
+}
+
+/// Classifies the lines of a shell session as they arrive.
+#[derive(Debug, Default)]
+pub(crate) struct SessionClassifier {
+    /// The previous line was part of a command behind a `$ ` or `# ` prompt,
+    /// which the continuation prompt continues.
+    in_shell_command: bool,
+    /// The previous line ended with a backslash.
+    continues: bool,
+}
+
+impl SessionClassifier {
+    /// Classify the next line of the session.
+    pub(crate) fn classify<'a>(&mut self, line: &'a str) -> SessionLine<'a> {
+        let continuation = line
+            .strip_prefix(CONTINUATION_PROMPT)
+            .filter(|_| self.in_shell_command)
+            .map(|command| (CONTINUATION_PROMPT, command))
+            .or_else(|| self.continues.then_some(("", line)));
+        let (prompt, command, continued) = match continuation {
+            Some((prompt, command)) => (prompt, command, true),
+            None => match PROMPTS
+                .iter()
+                .find_map(|prompt| Some((*prompt, line.strip_prefix(prompt)?)))
+            {
+                Some((prompt, command)) => {
+                    self.in_shell_command = prompt != CONTINUATION_PROMPT;
+                    (prompt, command, false)
+                }
+                None => {
+                    self.in_shell_command = false;
+                    self.continues = false;
+                    return SessionLine::Output;
+                }
+            },
+        };
+        self.continues = command.trim_end().ends_with('\\');
+        SessionLine::Command {
+            prompt,
+            command,
+            continued,
+        }
+    }
+}
+
+/// Extract the commands of a shell session, without their prompts.
+///
+/// Continued commands are joined with newlines, keeping any trailing
+/// backslashes so the result can be run as-is.
+pub(crate) fn session_commands(source: &str) -> Vec<String> {
+    let mut classifier = SessionClassifier::default();
+    let mut commands: Vec<String> = Vec::new();
+    for line in source.lines() {
+        if let SessionLine::Command {
+            command, continued, ..
+        } = classifier.classify(line)
+        {
+            match commands.last_mut().filter(|_| continued) {
+                Some(last) => {
+                    last.push('\n');
+                    last.push_str(command);
+                }
+                None => commands.push(command.to_string()),
+            }
+        }
+    }
+    commands
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commands_and_output() {
+        let source = "$ echo hi\nhi\n# whoami\nroot";
+        assert_eq!(session_commands(source), vec!["echo hi", "whoami"]);
+    }
+
+    #[test]
+    fn test_continued_commands() {
+        let source = "$ cargo build \\\n  --release\n$ echo \"a\n> b\"\na\nb";
+        assert_eq!(
+            session_commands(source),
+            vec!["cargo build \\\n  --release", "echo \"a\nb\""]
+        );
+    }
+
+    #[test]
+    fn test_continuation_prompt_as_primary_prompt() {
+        let mut classifier = SessionClassifier::default();
+        assert_eq!(
+            classifier.classify("> Get-Date"),
+            SessionLine::Command {
+                prompt: "> ",
+                command: "Get-Date",
+                continued: false
+            }
+        );
+        assert_eq!(classifier.classify("Friday"), SessionLine::Output);
+        assert!(matches!(
+            classifier.classify("$ ls"),
+            SessionLine::Command {
+                prompt: "$ ",
+                command: "ls",
+                continued: false
+            }
+        ));
+        assert_eq!(classifier.classify("file.txt"), SessionLine::Output);
+    }
+
+    #[test]
+    fn test_repl_commands_without_output() {
+        let source = "> let x = 1\n> x + 1\n2";
+        assert_eq!(session_commands(source), vec!["let x = 1", "x + 1"]);
+    }
+
+    #[test]
+    fn test_session_labels() {
+        assert!(is_session_label("console"));
+        assert!(is_session_label("Shell-Session"));
+        assert!(!is_session_label("bash"));
+    }
+}
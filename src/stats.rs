@@ -0,0 +1,20 @@
+//! Per-message render statistics.
+
+/// Statistics collected while rendering a single message.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RenderStats {
+    /// Number of complete input lines processed.
+    pub input_lines: usize,
+    /// Number of parse events rendered.
+    pub events: usize,
+    /// Number of lines written to the output.
+    pub output_lines: usize,
+    /// Number of bytes written to the output.
+    pub bytes_written: usize,
+    /// Number of headings rendered.
+    pub headings: usize,
+    /// Number of code blocks rendered.
+    pub code_blocks: usize,
+    /// Number of tables rendered.
+    pub tables: usize,
+}
@@ -5,6 +5,7 @@
 use colored::{Color, ColoredString, Colorize};
 use streamdown_parser::decode_html_entities;
 
+use crate::code::CodeTheme;
 use crate::style::{HeadingStyler, InlineStyler, ListStyler, TableStyler};
 
 /// Style configuration for a single element.
@@ -144,9 +145,32 @@ pub struct Theme {
 
     // Code block
     pub code_block_lang: Style,
+    pub code_block_border: Style,
+    pub code_collapsed: Style,
+    pub code_line_number: Style,
+    /// Style for emphasized lines; its background fills the whole line.
+    pub code_line_emphasis: Style,
+    /// Syntax highlighting theme for code blocks. `None` picks a bundled
+    /// theme matching `dark_background`.
+    pub code_theme: Option<CodeTheme>,
+    /// The theme is made for a dark terminal background.
+    pub dark_background: bool,
+
+    // Diff blocks; added and removed backgrounds fill the whole line
+    pub diff_added: Style,
+    pub diff_removed: Style,
+    pub diff_hunk: Style,
+    pub diff_file_header: Style,
+
+    // Shell session blocks
+    pub shell_prompt: Style,
+    pub shell_output: Style,
 
     // Horizontal rule
     pub hr: Style,
+
+    // Interrupted stream marker
+    pub interrupted: Style,
 }
 
 impl Default for Theme {
@@ -324,9 +348,28 @@ impl Theme {
 
             // Code block
             code_block_lang: Style::new().fg(Color::BrightBlack).italic(),
+            code_block_border: Style::new().fg(Color::BrightBlack),
+            code_collapsed: Style::new().fg(Color::BrightBlack).italic(),
+            code_line_number: Style::new().fg(Color::BrightBlack),
+            code_line_emphasis: Style::new().bg(Color::TrueColor { r: 62, g: 61, b: 40 }),
+            code_theme: None,
+            dark_background: true,
+
+            // Diff
+            diff_added: Style::new().fg(Color::Green).bg(Color::TrueColor { r: 24, g: 52, b: 30 }),
+            diff_removed: Style::new().fg(Color::Red).bg(Color::TrueColor { r: 64, g: 24, b: 28 }),
+            diff_hunk: Style::new().fg(Color::Cyan),
+            diff_file_header: Style::new().bold().bg(Color::TrueColor { r: 40, g: 44, b: 52 }),
+
+            // Shell session
+            shell_prompt: Style::new().fg(Color::Green).bold(),
+            shell_output: Style::new().dimmed(),
 
             // HR
             hr: Style::new().fg(Color::BrightBlack),
+
+            // Interrupted
+            interrupted: Style::new().fg(Color::Yellow).italic(),
         }
     }
 
@@ -371,9 +414,32 @@ impl Theme {
 
             // Code block
             code_block_lang: Style::new().fg(Color::Black).italic(),
+            code_block_border: Style::new().fg(Color::Black),
+            code_collapsed: Style::new().fg(Color::Black).italic(),
+            code_line_number: Style::new().fg(Color::Black).dimmed(),
+            code_line_emphasis: Style::new().bg(Color::TrueColor { r: 255, g: 247, b: 204 }),
+            code_theme: None,
+            dark_background: false,
+
+            // Diff
+            diff_added: Style::new()
+                .fg(Color::Green)
+                .bg(Color::TrueColor { r: 220, g: 255, b: 228 }),
+            diff_removed: Style::new()
+                .fg(Color::Red)
+                .bg(Color::TrueColor { r: 255, g: 224, b: 224 }),
+            diff_hunk: Style::new().fg(Color::Blue),
+            diff_file_header: Style::new().bold().bg(Color::TrueColor { r: 234, g: 236, b: 240 }),
+
+            // Shell session
+            shell_prompt: Style::new().fg(Color::Green).bold(),
+            shell_output: Style::new().dimmed(),
 
             // HR
             hr: Style::new().fg(Color::Black),
+
+            // Interrupted
+            interrupted: Style::new().fg(Color::Red).italic(),
         }
     }
 }
@@ -1,5 +1,7 @@
 //! Utility functions for the markdown renderer.
 
+use unicode_width::UnicodeWidthChar;
+
 /// Terminal theme mode (dark or light).
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ThemeMode {
@@ -11,11 +13,139 @@ pub enum ThemeMode {
 
 /// Detects the terminal theme mode (dark or light).
 pub fn detect_theme_mode() -> ThemeMode {
-    use terminal_colorsaurus::{theme_mode, QueryOptions};
     use terminal_colorsaurus::ThemeMode as ColorsaurusThemeMode;
+    use terminal_colorsaurus::{theme_mode, QueryOptions};
 
     match theme_mode(QueryOptions::default()) {
         Ok(ColorsaurusThemeMode::Light) => ThemeMode::Light,
         Ok(ColorsaurusThemeMode::Dark) | Err(_) => ThemeMode::Dark,
     }
 }
+
+/// Queries the width of the terminal attached to stdout.
+///
+/// Returns `None` if stdout is not a terminal.
+#[cfg(unix)]
+pub fn terminal_width() -> Option<usize> {
+    let mut size = libc::winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    // SAFETY: TIOCGWINSZ only writes into the provided winsize struct.
+    let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+    (result == 0 && size.ws_col > 0).then_some(size.ws_col as usize)
+}
+
+/// Queries the width of the terminal attached to stdout.
+///
+/// Always `None` on platforms without `ioctl` support.
+#[cfg(not(unix))]
+pub fn terminal_width() -> Option<usize> {
+    None
+}
+
+/// Standard base64 alphabet.
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encodes bytes as padded standard base64.
+pub fn base64_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, byte)| n | (u32::from(*byte) << (16 - 8 * i)));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64_ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Builds an OSC 52 sequence asking the terminal to copy text to the
+/// system clipboard.
+pub fn osc52_copy(text: &str) -> String {
+    format!("\x1b]52;c;{}\x07", base64_encode(text.as_bytes()))
+}
+
+/// Cuts text containing ANSI escape sequences to at most `width` display
+/// columns.
+///
+/// Escape sequences take no columns and are kept up to the cut; any after it
+/// are dropped, so callers should reset styles afterwards.
+pub fn truncate_visible(s: &str, width: usize) -> &str {
+    let mut used = 0;
+    let mut chars = s.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c == '\x1b' {
+            match chars.next() {
+                // CSI: parameters up to a final byte in `@`..=`~`
+                Some((_, '[')) => {
+                    for (_, c) in chars.by_ref() {
+                        if ('@'..='~').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+                // OSC: up to BEL or ST (`ESC \`)
+                Some((_, ']')) => {
+                    while let Some((_, c)) = chars.next() {
+                        if c == '\x07' {
+                            break;
+                        }
+                        if c == '\x1b' && chars.next_if(|&(_, c)| c == '\\').is_some() {
+                            break;
+                        }
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+        used += c.width().unwrap_or(0);
+        if used > width {
+            return &s[..i];
+        }
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64_encode() {
+        assert_eq!(base64_encode(b""), "");
+        assert_eq!(base64_encode(b"f"), "Zg==");
+        assert_eq!(base64_encode(b"fo"), "Zm8=");
+        assert_eq!(base64_encode(b"foo"), "Zm9v");
+        assert_eq!(base64_encode(b"foobar"), "Zm9vYmFy");
+        assert_eq!(base64_encode(&[0xff, 0xfe, 0x00]), "//4A");
+    }
+
+    #[test]
+    fn test_osc52_copy() {
+        assert_eq!(osc52_copy("ls -la"), "\x1b]52;c;bHMgLWxh\x07");
+    }
+
+    #[test]
+    fn test_truncate_visible() {
+        assert_eq!(truncate_visible("hello", 10), "hello");
+        assert_eq!(truncate_visible("hello", 3), "hel");
+        assert_eq!(truncate_visible("\x1b[1mhello\x1b[0m", 2), "\x1b[1mhe");
+        assert_eq!(truncate_visible("ab日本", 3), "ab");
+        let link = "\x1b]8;;https://example.com\x1b\\link\x1b]8;;\x1b\\";
+        assert_eq!(
+            truncate_visible(link, 2),
+            "\x1b]8;;https://example.com\x1b\\li"
+        );
+    }
+}